@@ -1,123 +1,146 @@
 extern crate chrono;
 use super::config::ClientConfig;
-use super::{gist::get_gist_file, gist::GistUpdate, gist::ListGist};
-use crate::event::{KeyEvent, KeyCode, KeyModifiers};
+use super::merge::{self, MergeSide};
+use super::storage::{self, ProjectConflict, SyncQueue, SyncStatus};
+use crate::command::{Completion, VimCommand};
+use crate::event::{
+    KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+use crate::keymap::{Action, Keymap};
 use chrono::prelude::*;
-use core::str::SplitWhitespace;
-use futures::executor;
+use chrono::Duration;
 use num_enum::TryFromPrimitive;
+use ratatui::layout::Rect;
+use regex::{Regex, RegexBuilder};
 use ropey::Rope;
 use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
 use std::fmt;
 use std::fmt::Write;
-use tokio::task;
-use ratatui::layout::Rect;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration as StdDuration, Instant};
 use unicode_width::UnicodeWidthChar;
 use uuid::Uuid;
 
-pub fn parse_text_parts(parts: &mut SplitWhitespace) -> Option<String> {
-    // Parse text parts and nest them together
-    let mut text_raw = String::new();
-
-    for text_part in parts {
-        if !text_raw.is_empty() {
-            text_raw.push_str(" ");
+/// Parses a due date relative to `today`, `None` clears the due date.
+///
+/// Accepts `2026-11-03`, `today`, `tomorrow`, a weekday like `fri` for the
+/// next one to come, or an offset like `+3d` or `-1w`.
+pub fn parse_due_date(
+    input: &str,
+    today: NaiveDate,
+) -> Result<Option<NaiveDate>, String> {
+    let input = input.to_lowercase();
+    let due = match input.as_str() {
+        "none" | "-" => return Ok(None),
+        "today" => today,
+        "tomorrow" => today + Duration::days(1),
+        _ => {
+            if let Ok(date) = NaiveDate::parse_from_str(&input, "%Y-%m-%d") {
+                date
+            } else if let Ok(weekday) = input.parse::<Weekday>() {
+                let days = (7 + weekday.num_days_from_monday()
+                    - today.weekday().num_days_from_monday())
+                    % 7;
+                today + Duration::days(if days == 0 { 7 } else { days.into() })
+            } else if input.starts_with('+') || input.starts_with('-') {
+                let invalid = || format!("invalid due date: {}", input);
+                let unit = input.chars().next_back().ok_or_else(invalid)?;
+                let count = input
+                    .strip_suffix(unit)
+                    .and_then(|count| count.parse::<i64>().ok())
+                    .ok_or_else(invalid)?;
+                let offset = match unit {
+                    'd' => Duration::try_days(count),
+                    'w' => Duration::try_weeks(count),
+                    _ => return Err(invalid()),
+                };
+                offset
+                    .and_then(|offset| today.checked_add_signed(offset))
+                    .ok_or_else(|| String::from("due date out of range"))?
+            } else {
+                return Err(format!("invalid due date: {}", input));
+            }
         }
-
-        text_raw.push_str(text_part);
-    }
-
-    if text_raw.is_empty() {
-        None
-    } else {
-        Some(text_raw)
-    }
+    };
+    Ok(Some(due))
 }
 
-#[derive(PartialEq, Eq, Clone, Hash, Debug)]
-pub enum VimCommand {
-    TaskRename(usize, String),
-    TaskDelete(usize),
-    TaskSetPriority(usize, usize),
-    ProjectNew(String),
-    ProjectOpen(String),
-    ProjectSave,
-    ProjectSaveAndQuit,
-    ShowToday(bool),
-    ShowFinished(bool),
-    Quit,
-    None,
+fn compute_character_width(character: char) -> u16 {
+    UnicodeWidthChar::width(character)
+        .unwrap()
+        .try_into()
+        .unwrap()
 }
 
-impl VimCommand {
-    pub fn from_command(cmd: String) -> VimCommand {
-        let mut tokens = cmd.split_whitespace();
-        let c = match tokens.next() {
-            Some(c) => &c[1..],
-            None => "",
-        };
-
-        match c {
-            "q" => VimCommand::Quit,
-            "w" => VimCommand::ProjectSave,
-            "wq" => VimCommand::ProjectSaveAndQuit,
-            "tmod" => {
-                let index = tokens.next().unwrap();
-                let content = parse_text_parts(&mut tokens)
-                    .unwrap_or_else(|| String::from("Invalid"));
-                VimCommand::TaskRename(index.parse::<usize>().unwrap(), content)
-            }
-            "tdel" => {
-                let index = tokens.next().unwrap();
-                VimCommand::TaskDelete(index.parse::<usize>().unwrap())
-            }
-            "tp" => {
-                let index = tokens.next().unwrap();
-                let value = tokens.next().unwrap();
-                VimCommand::TaskSetPriority(
-                    index.parse::<usize>().unwrap(),
-                    value.parse::<usize>().unwrap(),
-                )
-            }
-            "o" => {
-                let name = tokens.next().unwrap();
-                VimCommand::ProjectOpen(String::from(name))
-            }
-            "n" => {
-                let name = tokens.next().unwrap();
-                VimCommand::ProjectNew(String::from(name))
-            }
-            "sf" => {
-                let name = tokens.next().unwrap();
-                VimCommand::ShowFinished(name.parse::<bool>().unwrap())
-            }
-            "st" => {
-                let name = tokens.next().unwrap();
-                VimCommand::ShowToday(name.parse::<bool>().unwrap())
+pub fn on_down_press_handler<T>(
+    selection_data: &[T],
+    selection_index: Option<usize>,
+) -> usize {
+    match selection_index {
+        Some(selection_index) => {
+            if !selection_data.is_empty() {
+                let next_index = selection_index + 1;
+                if next_index > selection_data.len() - 1 {
+                    return 0;
+                } else {
+                    return next_index;
+                }
             }
-            _ => VimCommand::None,
+            0
         }
+        None => 0,
     }
 }
 
-impl From<String> for VimCommand {
-    fn from(command: String) -> Self {
-        VimCommand::from_command(command)
+pub fn on_up_press_handler<T>(
+    selection_data: &[T],
+    selection_index: Option<usize>,
+) -> usize {
+    match selection_index {
+        Some(selection_index) => {
+            if !selection_data.is_empty() {
+                if selection_index > 0 {
+                    return selection_index - 1;
+                } else {
+                    return selection_data.len() - 1;
+                }
+            }
+            0
+        }
+        None => 0,
     }
 }
 
-fn compute_character_width(character: char) -> u16 {
-    UnicodeWidthChar::width(character)
-        .unwrap()
-        .try_into()
-        .unwrap()
+/// What the frontend has to do after `App::handle_key`
+#[derive(Debug, PartialEq, Eq)]
+pub enum AppAction {
+    /// Only the screen needs drawing again
+    Redraw,
+    /// Back in Global mode, where there is no cursor to show
+    HideCursor,
+    /// Let the user edit the notes of `id` outside the UI and pass the
+    /// result to `App::set_task_notes`
+    EditNotes {
+        id: String,
+        notes: String,
+    },
+    Quit,
 }
 
+/// A single line input with readline style editing
 pub struct VimBar {
     buffer: Rope,
+    /// Cursor as a char index into `buffer`
     input_idx: usize,
-    input_cursor_position: u16,
+    /// Text removed by the last kill, put back with Ctrl-y
+    kill_buffer: String,
 }
 
 pub enum VimCommandBarResult {
@@ -126,12 +149,17 @@ pub enum VimCommandBarResult {
     Finished(String),
 }
 
+/// Whether `c` is part of a word for Alt-b, Alt-f and Alt-d
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
 impl VimBar {
     pub fn new() -> Self {
         VimBar {
             buffer: Rope::new(),
             input_idx: 0,
-            input_cursor_position: 0,
+            kill_buffer: String::new(),
         }
     }
 
@@ -139,75 +167,150 @@ impl VimBar {
         &self.buffer
     }
 
+    /// Screen column of the cursor, counting wide characters as two
     pub fn input_cursor_position(&self) -> u16 {
-        self.input_cursor_position
+        self.buffer
+            .chars()
+            .take(self.input_idx)
+            .map(compute_character_width)
+            .sum()
     }
 
     pub fn handle_input(&mut self, key: KeyEvent) -> VimCommandBarResult {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        let len = self.buffer.len_chars();
+
         match key.code {
-            KeyCode::Left => {
-                if self.buffer.len_chars() > 0 && self.input_idx > 0 {
-                    let last_c = self.buffer.char(self.input_idx - 1);
-                    self.input_idx -= 1;
-                    self.input_cursor_position -=
-                        compute_character_width(last_c);
-                }
-                VimCommandBarResult::StillEditing
-            }
-            KeyCode::Char(c) => {
-                self.buffer.insert_char(self.input_idx, c);
-                self.input_idx += 1;
-                self.input_cursor_position += compute_character_width(c);
-                VimCommandBarResult::StillEditing
-            }
             KeyCode::Enter => {
                 let mut result = String::new();
                 write!(result, "{}", self.buffer).unwrap();
                 self.clear();
-                VimCommandBarResult::Finished(result)
+                return VimCommandBarResult::Finished(result);
+            }
+            KeyCode::Backspace if len == 0 => {
+                return VimCommandBarResult::Aborted
+            }
+
+            // Motions
+            KeyCode::Left if ctrl => self.input_idx = self.word_start(),
+            KeyCode::Right if ctrl => self.input_idx = self.word_end(),
+            KeyCode::Char('b') if alt => self.input_idx = self.word_start(),
+            KeyCode::Char('f') if alt => self.input_idx = self.word_end(),
+            KeyCode::Left => self.input_idx = self.input_idx.saturating_sub(1),
+            KeyCode::Char('b') if ctrl => {
+                self.input_idx = self.input_idx.saturating_sub(1)
+            }
+            KeyCode::Right => self.input_idx = (self.input_idx + 1).min(len),
+            KeyCode::Char('f') if ctrl => {
+                self.input_idx = (self.input_idx + 1).min(len)
+            }
+            KeyCode::Home => self.input_idx = 0,
+            KeyCode::Char('a') if ctrl => self.input_idx = 0,
+            KeyCode::End => self.input_idx = len,
+            KeyCode::Char('e') if ctrl => self.input_idx = len,
+
+            // Deletion
+            KeyCode::Backspace if alt => {
+                self.kill(self.word_start(), self.input_idx)
             }
             KeyCode::Backspace => {
-                match (self.buffer.len_chars() == 0, self.input_idx == 0) {
-                    (true, _) => return VimCommandBarResult::Aborted,
-                    (false, true) => {}
-                    (false, false) => {
-                        self.input_cursor_position -= compute_character_width(
-                            self.buffer.char(self.input_idx - 1),
-                        );
-                        self.buffer.remove(self.input_idx - 1..self.input_idx);
-                        self.input_idx -= 1;
-                    }
-                }
-                VimCommandBarResult::StillEditing
+                self.delete(self.input_idx.saturating_sub(1), self.input_idx)
+            }
+            KeyCode::Char('h') if ctrl => {
+                self.delete(self.input_idx.saturating_sub(1), self.input_idx)
             }
             KeyCode::Delete => {
-                let len = self.buffer.len_chars();
-                if self.input_idx < len {
-                    self.buffer.remove(self.input_idx..=self.input_idx);
-                }
-                VimCommandBarResult::StillEditing
+                self.delete(self.input_idx, (self.input_idx + 1).min(len))
             }
-            KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                self.clear();
-                VimCommandBarResult::StillEditing
+            KeyCode::Char('d') if alt => {
+                self.kill(self.input_idx, self.word_end())
             }
-            KeyCode::Char('a') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                self.goto_being();
-                VimCommandBarResult::StillEditing
+            KeyCode::Char('w') if ctrl => {
+                self.kill(self.whitespace_word_start(), self.input_idx)
             }
-            _ => VimCommandBarResult::StillEditing,
+            KeyCode::Char('k') if ctrl => self.kill(self.input_idx, len),
+            KeyCode::Char('u') if ctrl => self.kill(0, self.input_idx),
+            KeyCode::Char('y') if ctrl => {
+                let text = self.kill_buffer.clone();
+                self.insert(&text);
+            }
+
+            // Anything else with a modifier is not text
+            KeyCode::Char(_) if ctrl || alt => {}
+            KeyCode::Char(c) => self.insert(c.encode_utf8(&mut [0; 4])),
+            _ => {}
         }
+        VimCommandBarResult::StillEditing
     }
 
     pub fn clear(&mut self) {
         self.buffer = Rope::new();
         self.input_idx = 0;
-        self.input_cursor_position = 0;
     }
 
-    pub fn goto_being(&mut self) {
-        self.input_idx = 0;
-        self.input_cursor_position = 0;
+    /// Replaces the input with `text`, the cursor goes to the end
+    pub fn set_buffer(&mut self, text: &str) {
+        self.buffer = Rope::from_str(text);
+        self.input_idx = self.buffer.len_chars();
+    }
+
+    fn insert(&mut self, text: &str) {
+        self.buffer.insert(self.input_idx, text);
+        self.input_idx += text.chars().count();
+    }
+
+    /// Removes the chars `start..end`, the cursor ends up at `start`
+    fn delete(&mut self, start: usize, end: usize) {
+        if start < end {
+            self.buffer.remove(start..end);
+            self.input_idx = start;
+        }
+    }
+
+    /// Like `delete` but the text can be yanked back
+    fn kill(&mut self, start: usize, end: usize) {
+        if start < end {
+            self.kill_buffer = self.buffer.slice(start..end).to_string();
+            self.delete(start, end);
+        }
+    }
+
+    /// Start of the word before the cursor
+    fn word_start(&self) -> usize {
+        let mut idx = self.input_idx;
+        while idx > 0 && !is_word_char(self.buffer.char(idx - 1)) {
+            idx -= 1;
+        }
+        while idx > 0 && is_word_char(self.buffer.char(idx - 1)) {
+            idx -= 1;
+        }
+        idx
+    }
+
+    /// End of the word after the cursor
+    fn word_end(&self) -> usize {
+        let len = self.buffer.len_chars();
+        let mut idx = self.input_idx;
+        while idx < len && !is_word_char(self.buffer.char(idx)) {
+            idx += 1;
+        }
+        while idx < len && is_word_char(self.buffer.char(idx)) {
+            idx += 1;
+        }
+        idx
+    }
+
+    /// Start of the whitespace separated word before the cursor, for Ctrl-w
+    fn whitespace_word_start(&self) -> usize {
+        let mut idx = self.input_idx;
+        while idx > 0 && self.buffer.char(idx - 1).is_whitespace() {
+            idx -= 1;
+        }
+        while idx > 0 && !self.buffer.char(idx - 1).is_whitespace() {
+            idx -= 1;
+        }
+        idx
     }
 }
 
@@ -221,6 +324,77 @@ pub enum ItemStatus {
     WontFix,
 }
 
+impl fmt::Display for ItemStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ItemStatus::Started => write!(f, "Started"),
+            ItemStatus::Finished => write!(f, "Finished"),
+            ItemStatus::UnStarted => write!(f, "UnStarted"),
+            ItemStatus::WontFix => write!(f, "WontFix"),
+        }
+    }
+}
+
+impl FromStr for ItemStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "started" => Ok(ItemStatus::Started),
+            "finished" | "done" => Ok(ItemStatus::Finished),
+            "unstarted" | "todo" => Ok(ItemStatus::UnStarted),
+            "wontfix" | "wont" => Ok(ItemStatus::WontFix),
+            _ => Err(format!("unknown status: {}", s)),
+        }
+    }
+}
+
+/// Tags a task must have, and must not have, to show up in the view
+#[derive(PartialEq, Eq, Clone, Hash, Debug, Default)]
+pub struct TagFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl TagFilter {
+    /// Parses `+tag` and `-+tag` tokens, the `+` is optional
+    pub fn from_tokens<'a, I>(tokens: I) -> TagFilter
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut filter = TagFilter::default();
+        for token in tokens {
+            let (list, tag) = match token.strip_prefix('-') {
+                Some(tag) => (&mut filter.exclude, tag),
+                None => (&mut filter.include, token),
+            };
+            let tag = tag.trim_start_matches('+');
+            if !tag.is_empty() {
+                list.push(tag.to_lowercase());
+            }
+        }
+        filter
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn matches(&self, item: &WorkItem) -> bool {
+        self.include.iter().all(|t| item.tags.contains(t))
+            && !self.exclude.iter().any(|t| item.tags.contains(t))
+    }
+}
+
+impl fmt::Display for TagFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let include = self.include.iter().map(|t| format!("+{}", t));
+        let exclude = self.exclude.iter().map(|t| format!("-+{}", t));
+        let parts = include.chain(exclude).collect::<Vec<String>>();
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
 #[derive(Clone, PartialEq, Debug, Copy, TryFromPrimitive)]
 #[repr(usize)]
 pub enum AppFilterMode {
@@ -245,6 +419,9 @@ pub enum AppMode {
     Insert,
     Command,
     Global,
+    Resolve,
+    Search,
+    Help,
 }
 
 impl fmt::Display for AppMode {
@@ -253,15 +430,31 @@ impl fmt::Display for AppMode {
             AppMode::Insert => write!(f, "Insert"),
             AppMode::Command => write!(f, "Command"),
             AppMode::Global => write!(f, "Global"),
+            AppMode::Resolve => write!(f, "Resolve"),
+            AppMode::Search => write!(f, "Search"),
+            AppMode::Help => write!(f, "Help"),
         }
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Highest priority a task can have, `0` means no priority
+pub const MAX_PRIORITY: usize = 3;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct WorkItem {
     pub id: Option<String>,
     pub content: Option<String>,
     pub status: ItemStatus,
+    #[serde(default)]
+    pub priority: usize,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default, with = "option_day_format")]
+    pub due: Option<NaiveDate>,
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
     #[serde(with = "normal_date_format")]
     pub created_time: DateTime<Local>,
     #[serde(with = "option_date_format")]
@@ -271,7 +464,7 @@ pub struct WorkItem {
 }
 
 mod normal_date_format {
-    use chrono::{DateTime, Local, TimeZone};
+    use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
     use serde::{self, Deserialize, Deserializer, Serializer};
 
     const FORMAT: &str = "%Y-%m-%d %H:%M:%S";
@@ -294,14 +487,17 @@ mod normal_date_format {
         D: Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
+        let naive = NaiveDateTime::parse_from_str(&s, FORMAT)
+            .map_err(serde::de::Error::custom)?;
         Local
-            .datetime_from_str(&s, FORMAT)
-            .map_err(serde::de::Error::custom)
+            .from_local_datetime(&naive)
+            .earliest()
+            .ok_or_else(|| serde::de::Error::custom("invalid local time"))
     }
 }
 
 mod option_date_format {
-    use chrono::{DateTime, Local, TimeZone};
+    use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
     use serde::{self, Deserialize, Deserializer, Serializer};
 
     const FORMAT: &str = "%Y-%m-%d %H:%M:%S";
@@ -327,24 +523,85 @@ mod option_date_format {
         D: Deserializer<'de>,
     {
         match String::deserialize(deserializer) {
-            Ok(_r) => Ok(Some(Local.datetime_from_str(&_r, FORMAT).unwrap())),
+            Ok(_r) => Ok(NaiveDateTime::parse_from_str(&_r, FORMAT)
+                .ok()
+                .and_then(|d| Local.from_local_datetime(&d).earliest())),
             Err(_e) => Ok(None),
         }
     }
 }
 
+mod option_day_format {
+    use chrono::NaiveDate;
+    use serde::{self, Deserialize, Deserializer, Serializer};
+
+    const FORMAT: &str = "%Y-%m-%d";
+
+    pub fn serialize<S>(
+        date: &Option<NaiveDate>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if let Some(d) = date {
+            serializer.serialize_str(&d.format(FORMAT).to_string())
+        } else {
+            serializer.serialize_unit()
+        }
+    }
+
+    pub fn deserialize<'de, D>(
+        deserializer: D,
+    ) -> Result<Option<NaiveDate>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(s) => Ok(NaiveDate::parse_from_str(&s, FORMAT).ok()),
+            None => Ok(None),
+        }
+    }
+}
+
 impl WorkItem {
     pub fn new() -> WorkItem {
         WorkItem {
             id: None,
             content: None,
             status: ItemStatus::UnStarted,
+            priority: 0,
+            tags: Vec::new(),
+            due: None,
+            parent_id: None,
+            notes: None,
             created_time: Local::now(),
             started_time: None,
             finished_time: None,
         }
     }
 
+    /// Builds a task from insert bar text, `+tag` words become tags
+    pub fn from_input(text: &str) -> WorkItem {
+        let mut item = WorkItem::new();
+        let mut content = Vec::new();
+
+        for word in text.split_whitespace() {
+            match word.strip_prefix('+') {
+                Some(tag) if !tag.is_empty() => {
+                    let tag = tag.to_lowercase();
+                    if !item.tags.contains(&tag) {
+                        item.tags.push(tag);
+                    }
+                }
+                _ => content.push(word),
+            }
+        }
+
+        item.content = Some(content.join(" "));
+        item
+    }
+
     pub fn start(&mut self) {
         self.started_time = Some(Local::now());
         self.status = ItemStatus::Started;
@@ -360,17 +617,44 @@ impl WorkItem {
         self.status = ItemStatus::WontFix;
     }
 
+    pub fn reopen(&mut self) {
+        self.status = ItemStatus::UnStarted;
+        self.started_time = None;
+        self.finished_time = None;
+    }
+
+    /// Still open and due before `today`
+    pub fn is_overdue(&self, today: NaiveDate) -> bool {
+        match self.status {
+            ItemStatus::Started | ItemStatus::UnStarted => {
+                self.due.map(|d| d < today).unwrap_or(false)
+            }
+            _ => false,
+        }
+    }
+
+    /// Due today, overdue or started today
+    pub fn is_on_agenda(&self, today: NaiveDate) -> bool {
+        self.due == Some(today)
+            || self.is_overdue(today)
+            || self.started_time.map(|t| t.date_naive()) == Some(today)
+    }
+
     pub fn is_valid_for_mode(
         &self,
         a: AppFilterMode,
         sf: bool,
-        _st: bool,
+        st: bool,
     ) -> bool {
         // This is kind of gross but works for now
         if self.status == ItemStatus::Finished && !sf {
             return false;
         }
 
+        if st && !self.is_on_agenda(Local::now().date_naive()) {
+            return false;
+        }
+
         match a {
             AppFilterMode::All => true,
             AppFilterMode::Started => self.status == ItemStatus::Started,
@@ -380,227 +664,1833 @@ impl WorkItem {
     }
 }
 
-pub struct App {
-    pub tasks: Vec<WorkItem>,
-    pub size: Rect,
-    pub selected_index: usize,
-    pub filter: AppFilterMode,
-    pub client_config: ClientConfig,
-    pub command_bar: VimBar,
-    pub insert_bar: VimBar,
-    pub mode: AppMode,
-    pub current_project: Option<String>,
-    pub current_file_list: Option<ListGist>,
-    pub register: Option<WorkItem>,
+/// Orders `items` so every task directly follows its parent, along with its
+/// depth in the tree. Tasks whose parent is not in `items` become roots.
+fn build_tree(
+    items: &[WorkItem],
+    collapsed: &HashSet<String>,
+) -> Vec<(usize, WorkItem)> {
+    let ids = items
+        .iter()
+        .filter_map(|t| t.id.as_deref())
+        .collect::<HashSet<&str>>();
+    let mut visited = HashSet::new();
+    let mut tree = Vec::new();
+
+    let roots = items.iter().filter(|t| match &t.parent_id {
+        Some(parent) => !ids.contains(parent.as_str()),
+        None => true,
+    });
+    for root in roots {
+        push_subtree(items, root, 0, false, collapsed, &mut visited, &mut tree);
+    }
+
+    // Anything left over is part of a parent cycle, show it flat
+    for item in items {
+        if item
+            .id
+            .as_ref()
+            .map(|id| !visited.contains(id))
+            .unwrap_or(false)
+        {
+            push_subtree(
+                items,
+                item,
+                0,
+                false,
+                collapsed,
+                &mut visited,
+                &mut tree,
+            );
+        }
+    }
+
+    tree
 }
 
-impl App {
-    pub fn new() -> App {
-        App {
-            tasks: Vec::new(),
-            size: Rect::default(),
-            selected_index: 0,
-            filter: AppFilterMode::All,
-            client_config: Default::default(),
-            command_bar: VimBar::new(),
-            insert_bar: VimBar::new(),
-            mode: AppMode::Global,
-            current_project: None,
-            current_file_list: None,
-            register: None,
+fn push_subtree(
+    items: &[WorkItem],
+    item: &WorkItem,
+    depth: usize,
+    hidden: bool,
+    collapsed: &HashSet<String>,
+    visited: &mut HashSet<String>,
+    tree: &mut Vec<(usize, WorkItem)>,
+) {
+    let id = match &item.id {
+        Some(id) => id,
+        None => {
+            tree.push((depth, item.clone()));
+            return;
         }
+    };
+    if !visited.insert(id.clone()) {
+        return;
+    }
+    if !hidden {
+        tree.push((depth, item.clone()));
     }
 
-    pub async fn init(&mut self) {
-        self.refresh_projects().await;
-        self.current_project = self.client_config.current_project.to_owned();
+    let hidden = hidden || collapsed.contains(id);
+    for child in items.iter().filter(|c| c.parent_id.as_ref() == Some(id)) {
+        push_subtree(items, child, depth + 1, hidden, collapsed, visited, tree);
     }
+}
 
-    pub async fn sync(&mut self) {
-        if let (Some(list), Some(proj)) =
-            (&self.current_file_list, &self.current_project)
-        {
-            if let Ok(gist) =
-                list.get_url_gist_file(&self.client_config.client_id, &proj)
-            {
-                let data =
-                    get_gist_file(&gist, &self.client_config.client_secret)
-                        .await
-                        .unwrap();
-                let actual_data: Vec<WorkItem> =
-                    serde_json::from_str(&data).unwrap();
-                self.tasks = actual_data;
-            }
+/// Picks the side to keep for every conflict of a project
+pub struct Resolution {
+    pub project: String,
+    pub conflict: ProjectConflict,
+    pub choices: Vec<Option<MergeSide>>,
+    pub selected_index: usize,
+}
+
+impl Resolution {
+    pub fn new(project: String, conflict: ProjectConflict) -> Self {
+        let choices = vec![None; conflict.merged.conflicts.len()];
+        Resolution {
+            project,
+            conflict,
+            choices,
+            selected_index: 0,
         }
     }
 
-    fn find_and_set_project(&mut self, project: &str) -> bool {
-        self.get_projects()
-            .iter()
-            .find(|v| v == &project)
-            .and_then(|x| {
-                self.current_project = Some(project.to_string());
-                Some(x)
-            })
-            .is_some()
+    pub fn choose(&mut self, side: MergeSide) {
+        if let Some(choice) = self.choices.get_mut(self.selected_index) {
+            *choice = Some(side);
+        }
     }
 
-    #[allow(unused_must_use)]
-    pub async fn select_project(&mut self, project: &str) {
-        if !self.find_and_set_project(project) {
-            self.refresh_projects().await;
-            self.find_and_set_project(project);
+    pub fn choose_all(&mut self, side: MergeSide) {
+        for choice in self.choices.iter_mut() {
+            *choice = Some(side);
         }
+    }
 
-        self.client_config.current_project = self.current_project.to_owned();
-        self.client_config.save_config();
+    /// The resolved tasks, once a side was picked for every conflict
+    pub fn resolved(&self) -> Option<Vec<WorkItem>> {
+        let choices =
+            self.choices.iter().copied().collect::<Option<Vec<_>>>()?;
+        Some(merge::resolve(
+            &self.conflict.merged.tasks,
+            &self.conflict.merged.conflicts,
+            &choices,
+        ))
     }
+}
 
-    #[allow(unused_must_use)]
-    pub async fn new_project(&mut self, project: &str) {
-        self.current_project = Some(project.to_string());
-        self.client_config.current_project = self.current_project.to_owned();
-        self.client_config.save_config();
+/// A `/` search over task content and tags. The pattern is matched
+/// literally unless it contains `\v`, and `\c` makes it ignore case.
+pub struct Search {
+    matcher: Regex,
+}
 
-        self.tasks.drain(..);
-        self.save_project(false, true);
-    }
+impl Search {
+    pub fn new(input: &str) -> Result<Search, regex::Error> {
+        let regex = input.contains("\\v");
+        let ignore_case = input.contains("\\c");
+        let pattern = input.replace("\\v", "").replace("\\c", "");
+        let pattern = if regex {
+            pattern
+        } else {
+            regex::escape(&pattern)
+        };
 
-    pub fn save_project(&mut self, wait: bool, sync: bool) {
-        if let (Some(ref proj), Some(list)) =
-            (&self.current_project, &self.current_file_list)
-        {
-            let s = ::serde_json::to_string(&self.tasks).unwrap();
-            let client_id = self.client_config.client_id.to_owned();
-            let client_secret = self.client_config.client_secret.to_owned();
-            let proj_copy = proj.clone();
-            let list_copy = list.clone();
-            let thing = async move {
-                let gg = GistUpdate::new(
-                    s,
-                    proj_copy.to_string(),
-                    proj_copy.to_string(),
-                    Some(proj_copy.to_string()),
-                );
+        let matcher = RegexBuilder::new(&pattern)
+            .case_insensitive(ignore_case)
+            .build()?;
+        Ok(Search { matcher })
+    }
 
-                let file = list_copy.search_url_gist(&client_id).unwrap();
+    pub fn is_match(&self, item: &WorkItem) -> bool {
+        item.content
+            .as_deref()
+            .map(|c| self.matcher.is_match(c))
+            .unwrap_or(false)
+            || item.tags.iter().any(|t| self.matcher.is_match(t))
+    }
+}
 
-                gg.update(&file, &client_secret).await
-            };
+/// Count prefix and keys typed so far of a Global mode command like `3dd`
+#[derive(Default)]
+pub struct KeySequence {
+    count: Option<usize>,
+    keys: Vec<KeyEvent>,
+}
 
-            if wait {
-                let _ = executor::block_on::<_>(thing);
-            } else {
-                task::spawn(thing);
+impl KeySequence {
+    /// Adds `key` and returns the count and keys once they form a whole
+    /// command. `is_prefix` tells if the keys so far start a longer command.
+    pub fn push<F>(
+        &mut self,
+        key: KeyEvent,
+        is_prefix: F,
+    ) -> Option<(Option<usize>, Vec<KeyEvent>)>
+    where
+        F: Fn(&[KeyEvent]) -> bool,
+    {
+        if let KeyCode::Char(c) = key.code {
+            let digit = c.to_digit(10).filter(|_| {
+                !key.modifiers.contains(KeyModifiers::CONTROL)
+                    && self.keys.is_empty()
+            });
+            // A leading 0 is not a count
+            if let Some(digit) =
+                digit.filter(|d| *d > 0 || self.count.is_some())
+            {
+                let count = self.count.unwrap_or(0);
+                self.count = Some(
+                    count.saturating_mul(10).saturating_add(digit as usize),
+                );
+                return None;
             }
+        }
 
-            if sync {
-                executor::block_on(self.init());
-            }
+        self.keys.push(key);
+        if is_prefix(&self.keys) {
+            return None;
         }
+        Some((self.count.take(), std::mem::take(&mut self.keys)))
     }
 
-    pub async fn refresh_projects(&mut self) {
-        self.current_file_list = Some(
-            ListGist::get_update_list_gist(&self.client_config.client_secret)
-                .await
-                .unwrap(),
-        );
+    pub fn clear(&mut self) {
+        self.count = None;
+        self.keys.clear();
     }
 
-    pub fn get_projects(&self) -> Vec<String> {
-        if let Some(list) = &self.current_file_list {
-            let gist = list.search_gist(&self.client_config.client_id).unwrap();
-            return gist
-                .files
-                .iter()
-                .map(|(_, file)| file.name.clone())
-                .collect::<Vec<String>>();
+    /// What has been typed so far, shown while waiting for the rest
+    pub fn pending(&self) -> String {
+        let mut pending = self.count.map(|c| c.to_string()).unwrap_or_default();
+        for key in &self.keys {
+            if let KeyCode::Char(c) = key.code {
+                pending.push(c);
+            }
         }
-
-        Vec::new()
+        pending
     }
+}
 
-    pub fn get_cursor_position(&self) -> u16 {
-        match self.mode {
-            AppMode::Global => 0,
-            AppMode::Command => self.command_bar.input_cursor_position(),
-            AppMode::Insert => self.insert_bar.input_cursor_position(),
+/// How many edits `u` can step back through
+const HISTORY_LIMIT: usize = 100;
+
+/// Snapshots of the task list taken before every edit
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Vec<WorkItem>>,
+    redo: Vec<Vec<WorkItem>>,
+}
+
+impl History {
+    /// Remembers `tasks` as they were before an edit
+    pub fn record(&mut self, tasks: &[WorkItem]) {
+        if self.undo.len() == HISTORY_LIMIT {
+            self.undo.remove(0);
         }
+        self.undo.push(tasks.to_vec());
+        self.redo.clear();
     }
 
-    pub fn update_work_item_text(&mut self, id: &str, content: &str) {
-        if let Some(task) =
-            self.tasks.iter_mut().find(|s| s.id == Some(id.to_string()))
-        {
-            task.content = Some(content.to_string())
-        }
+    pub fn undo(&mut self, current: &[WorkItem]) -> Option<Vec<WorkItem>> {
+        let tasks = self.undo.pop()?;
+        self.redo.push(current.to_vec());
+        Some(tasks)
     }
 
-    pub fn get_view(&self) -> Vec<WorkItem> {
-        self.tasks
-            .iter()
-            .filter(|l| {
-                l.is_valid_for_mode(
-                    self.filter,
-                    self.client_config.show_finished.unwrap(),
-                    self.client_config.show_today.unwrap(),
-                )
-            })
-            .map(|m| m)
-            .cloned()
-            .collect()
+    pub fn redo(&mut self, current: &[WorkItem]) -> Option<Vec<WorkItem>> {
+        let tasks = self.redo.pop()?;
+        self.undo.push(current.to_vec());
+        Some(tasks)
     }
 
-    pub fn start_task(&mut self, id: &str) {
-        if let Some(task) =
-            self.tasks.iter_mut().find(|s| s.id == Some(id.to_string()))
-        {
-            task.start()
-        }
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
     }
+}
 
-    pub fn finish_task(&mut self, id: &str) {
-        if let Some(task) =
-            self.tasks.iter_mut().find(|s| s.id == Some(id.to_string()))
+/// Longest time between two clicks on a task that still opens its details
+const DOUBLE_CLICK_INTERVAL: StdDuration = StdDuration::from_millis(400);
+
+/// Where the task rows were last drawn, to find the task under the mouse
+#[derive(Clone, Copy, Default)]
+pub struct TableLayout {
+    /// Screen area of the rows below the header
+    pub rows: Rect,
+    /// Index of the task in the first row
+    pub offset: usize,
+    /// Screen column and width of the status cells
+    pub status_column: (u16, u16),
+}
+
+impl TableLayout {
+    /// Index in the view of the task drawn at `column`, `row`
+    pub fn task_at(&self, column: u16, row: u16) -> Option<usize> {
+        let rows = self.rows;
+        if column < rows.left()
+            || column >= rows.right()
+            || row < rows.top()
+            || row >= rows.bottom()
         {
-            task.finish()
+            return None;
         }
+        Some(self.offset + usize::from(row - rows.top()))
     }
 
-    pub fn wont_task(&mut self, id: &str) {
-        if let Some(task) =
-            self.tasks.iter_mut().find(|s| s.id == Some(id.to_string()))
+    pub fn is_status(&self, column: u16) -> bool {
+        let (x, width) = self.status_column;
+        column >= x && column < x + width
+    }
+}
+
+/// How many `:` commands are kept across sessions
+const COMMAND_HISTORY_LIMIT: usize = 500;
+
+/// Previous `:` commands, recalled with Up and Down
+#[derive(Default)]
+pub struct CommandHistory {
+    entries: Vec<String>,
+    /// Entry shown in the command bar, `None` while typing a new one
+    position: Option<usize>,
+    /// What was typed before moving into the history
+    draft: String,
+    path: Option<PathBuf>,
+}
+
+impl CommandHistory {
+    /// Reads the history saved at `path`, a missing file is an empty history
+    pub fn load(path: PathBuf) -> anyhow::Result<Self> {
+        let entries = match fs::read_to_string(&path) {
+            Ok(content) => content.lines().map(String::from).collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(CommandHistory {
+            entries,
+            position: None,
+            draft: String::new(),
+            path: Some(path),
+        })
+    }
+
+    /// Remembers `command` and writes the history back to disk
+    pub fn push(&mut self, command: &str) -> anyhow::Result<()> {
+        self.reset();
+        if command.trim().is_empty() {
+            return Ok(());
+        }
+        // Running a command again moves it to the end
+        self.entries.retain(|e| e != command);
+        self.entries.push(command.to_string());
+        if self.entries.len() > COMMAND_HISTORY_LIMIT {
+            self.entries.remove(0);
+        }
+
+        if let Some(path) = &self.path {
+            let mut content = self.entries.join("\n");
+            content.push('\n');
+            fs::write(path, content)?;
+        }
+        Ok(())
+    }
+
+    /// The entry before the one shown, `current` is kept to come back to
+    pub fn previous(&mut self, current: &str) -> Option<&str> {
+        let position = match self.position {
+            None if self.entries.is_empty() => return None,
+            None => {
+                self.draft = current.to_string();
+                self.entries.len() - 1
+            }
+            Some(0) => 0,
+            Some(position) => position - 1,
+        };
+        self.position = Some(position);
+        Some(&self.entries[position])
+    }
+
+    /// The entry after the one shown, or what was typed after the last one
+    pub fn next(&mut self) -> Option<&str> {
+        let position = self.position?;
+        if position + 1 < self.entries.len() {
+            self.position = Some(position + 1);
+            Some(&self.entries[position + 1])
+        } else {
+            self.position = None;
+            Some(&self.draft)
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.position = None;
+        self.draft.clear();
+    }
+}
+
+pub struct App {
+    pub tasks: Vec<WorkItem>,
+    pub size: Rect,
+    /// Filtered, sorted and folded tasks along with their depth, as the
+    /// table shows them. Rebuilt by `refresh_view`.
+    view: Vec<(usize, WorkItem)>,
+    /// Id of the selected task, it stays selected while the view changes
+    selected: Option<String>,
+    /// Where `selected` is in `view`
+    selected_index: usize,
+    pub filter: AppFilterMode,
+    pub tag_filter: TagFilter,
+    pub client_config: ClientConfig,
+    pub command_bar: VimBar,
+    pub command_history: CommandHistory,
+    /// Candidates shown above the command bar after Tab
+    pub completion: Option<Completion>,
+    pub insert_bar: VimBar,
+    pub search_bar: VimBar,
+    pub search: Option<Search>,
+    pub key_sequence: KeySequence,
+    pub keymap: Keymap,
+    /// Selection to return to when a search is aborted
+    search_origin: usize,
+    pub mode: AppMode,
+    pub current_project: Option<String>,
+    pub projects: Vec<String>,
+    pub sync_queue: Arc<SyncQueue>,
+    pub resolution: Option<Resolution>,
+    pub status_message: Option<String>,
+    /// Why the last `:` command was rejected, until the next key press
+    pub command_error: Option<String>,
+    /// Project `:pdel` deletes once the next key press is `y`
+    pub pending_delete: Option<String>,
+    pub register: Vec<WorkItem>,
+    /// Parent of the task being typed in the insert bar
+    pub insert_parent: Option<String>,
+    pub collapsed: HashSet<String>,
+    pub show_details: bool,
+    /// First line of the help shown
+    pub help_scroll: usize,
+    /// Last line the help can scroll to, set while drawing it
+    pub help_max_scroll: Cell<usize>,
+    pub history: History,
+    /// Set while drawing the task table
+    pub table_layout: Cell<TableLayout>,
+    /// When and on which task the last click was, to spot double clicks
+    last_click: Option<(Instant, usize)>,
+}
+
+impl App {
+    pub fn new(client_config: ClientConfig) -> anyhow::Result<App> {
+        let sync_queue = storage::new_sync_queue(&client_config)?;
+        let paths = client_config.get_or_build_paths()?;
+        let command_history = CommandHistory::load(paths.command_history_path)?;
+        let keymap = match &client_config.keybindings {
+            Some(keybindings) => Keymap::new(keybindings)?,
+            None => Keymap::default(),
+        };
+
+        let mut app = App::with_sync_queue(client_config, sync_queue);
+        app.keymap = keymap;
+        app.command_history = command_history;
+        Ok(app)
+    }
+
+    pub fn with_sync_queue(
+        client_config: ClientConfig,
+        sync_queue: SyncQueue,
+    ) -> App {
+        App {
+            tasks: Vec::new(),
+            size: Rect::default(),
+            view: Vec::new(),
+            selected: None,
+            selected_index: 0,
+            filter: AppFilterMode::All,
+            tag_filter: TagFilter::default(),
+            client_config,
+            command_bar: VimBar::new(),
+            command_history: CommandHistory::default(),
+            completion: None,
+            insert_bar: VimBar::new(),
+            search_bar: VimBar::new(),
+            search: None,
+            search_origin: 0,
+            key_sequence: KeySequence::default(),
+            keymap: Keymap::default(),
+            mode: AppMode::Global,
+            current_project: None,
+            projects: Vec::new(),
+            sync_queue: Arc::new(sync_queue),
+            resolution: None,
+            status_message: None,
+            command_error: None,
+            pending_delete: None,
+            register: Vec::new(),
+            insert_parent: None,
+            collapsed: HashSet::new(),
+            show_details: false,
+            help_scroll: 0,
+            help_max_scroll: Cell::new(0),
+            history: History::default(),
+            table_layout: Cell::new(TableLayout::default()),
+            last_click: None,
+        }
+    }
+
+    /// Loads the current project from the cache and starts reconciling it
+    /// with the remote in the background
+    pub fn init(&mut self) {
+        self.load();
+        self.sync_queue.spawn_background();
+    }
+
+    /// Loads the current project from the cache
+    pub fn load(&mut self) {
+        self.current_project = self.client_config.current_project.to_owned();
+        self.refresh_projects();
+        self.reload_project();
+    }
+
+    /// Switches to `project` without remembering it in the config
+    pub fn open_project(&mut self, project: &str) {
+        self.current_project = Some(project.to_string());
+        self.history.clear();
+        self.reload_project();
+    }
+
+    /// Uploads queued edits and pulls remote changes right away
+    pub async fn sync(&mut self) {
+        self.status_message = None;
+        let _ = self.sync_queue.reconcile().await;
+        self.take_remote_changes();
+        self.refresh_projects();
+        self.reload_project();
+    }
+
+    /// Picks up anything the background reconciliation changed in the cache
+    pub fn poll_sync(&mut self) {
+        if self.take_remote_changes() {
+            self.refresh_projects();
+            self.reload_project();
+        }
+
+        if let Some(proj) = &self.current_project {
+            if self.sync_queue.take_new_conflict(proj) {
+                if let AppMode::Global = self.mode {
+                    self.open_resolution();
+                }
+            }
+        }
+    }
+
+    /// Returns whether remote changes replaced the cached copy of the
+    /// current project. Undoing past them would revert them, so the history
+    /// is dropped.
+    fn take_remote_changes(&mut self) -> bool {
+        let updated = match &self.current_project {
+            Some(proj) => self.sync_queue.take_updated(proj),
+            None => false,
+        };
+        if updated {
+            self.history.clear();
+        }
+        updated
+    }
+
+    /// Shows the conflicts of the current project, if there are any
+    pub fn open_resolution(&mut self) {
+        if let Some(proj) = &self.current_project {
+            if let Some(conflict) = self.sync_queue.conflict(proj) {
+                self.resolution = Some(Resolution::new(proj.clone(), conflict));
+                self.mode = AppMode::Resolve;
+            }
+        }
+    }
+
+    /// Stores the resolved tasks and queues their upload. Does nothing
+    /// until a side was picked for every conflict.
+    pub fn apply_resolution(&mut self) {
+        let tasks = match self.resolution.as_ref().and_then(|r| r.resolved()) {
+            Some(tasks) => tasks,
+            None => return,
+        };
+
+        if let Some(resolution) = self.resolution.take() {
+            let result = self.sync_queue.resolve(
+                &resolution.project,
+                &tasks,
+                &resolution.conflict.remote,
+            );
+            if self.report(result).is_some() {
+                if self.current_project.as_ref() == Some(&resolution.project) {
+                    self.tasks = tasks;
+                    self.refresh_view();
+                }
+                self.sync_queue.spawn_push();
+            }
+        }
+        self.mode = AppMode::Global;
+    }
+
+    pub fn sync_status(&self) -> SyncStatus {
+        self.sync_queue.status()
+    }
+
+    /// The last error worth telling the user about, a failing sync first
+    pub fn status_line(&self) -> Option<String> {
+        self.sync_queue
+            .last_error()
+            .or_else(|| self.status_message.clone())
+    }
+
+    fn report<T>(&mut self, result: anyhow::Result<T>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.status_message = Some(e.to_string());
+                None
+            }
+        }
+    }
+
+    fn reload_project(&mut self) {
+        if let Some(proj) = &self.current_project {
+            let result = self.sync_queue.load(proj);
+            if let Some(tasks) = self.report(result) {
+                self.tasks = tasks.unwrap_or_default();
+                self.refresh_view();
+            }
+        }
+    }
+
+    fn find_and_set_project(&mut self, project: &str) -> bool {
+        let found = self.get_projects().iter().any(|v| v == project);
+        if found {
+            self.current_project = Some(project.to_string());
+        }
+        found
+    }
+
+    #[allow(unused_must_use)]
+    pub fn select_project(&mut self, project: &str) {
+        if !self.find_and_set_project(project) {
+            self.refresh_projects();
+            self.find_and_set_project(project);
+        }
+
+        self.client_config.current_project = self.current_project.to_owned();
+        self.client_config.save_config();
+        self.history.clear();
+        self.reload_project();
+    }
+
+    #[allow(unused_must_use)]
+    pub fn new_project(&mut self, project: &str) {
+        self.current_project = Some(project.to_string());
+        self.client_config.current_project = self.current_project.to_owned();
+        self.client_config.save_config();
+
+        self.tasks.drain(..);
+        self.history.clear();
+        self.refresh_view();
+        let result = self.sync_queue.create(project);
+        if self.report(result).is_some() {
+            self.sync_queue.spawn_push();
+        }
+        self.refresh_projects();
+    }
+
+    #[allow(unused_must_use)]
+    pub fn delete_project(&mut self, project: &str) {
+        let result = self.sync_queue.delete(project);
+        if self.report(result).is_none() {
+            return;
+        }
+
+        if self.current_project.as_deref() == Some(project) {
+            self.current_project = None;
+            self.client_config.current_project = None;
+            self.client_config.save_config();
+            self.tasks.drain(..);
+            self.history.clear();
+            self.refresh_view();
+        }
+        self.refresh_projects();
+    }
+
+    /// Writes the current project to the cache and queues the upload
+    pub fn save_project(&mut self) {
+        let result = self.write_project();
+        if self.report(result).is_some() {
+            self.sync_queue.spawn_push();
+        }
+    }
+
+    /// Writes the current project to the cache and attempts the upload
+    /// before returning
+    pub async fn save_project_and_push(&mut self) {
+        let result = self.write_project();
+        if self.report(result).is_some() {
+            let _ = self.sync_queue.push().await;
+        }
+    }
+
+    fn write_project(&self) -> anyhow::Result<()> {
+        match &self.current_project {
+            Some(proj) => self.sync_queue.save(proj, &self.tasks),
+            None => Err(anyhow::anyhow!("no project selected")),
+        }
+    }
+
+    pub fn refresh_projects(&mut self) {
+        let result = self.sync_queue.list_projects();
+        if let Some(projects) = self.report(result) {
+            self.projects = projects;
+        }
+    }
+
+    pub fn get_projects(&self) -> Vec<String> {
+        self.projects.clone()
+    }
+
+    pub fn get_cursor_position(&self) -> u16 {
+        match self.mode {
+            AppMode::Global | AppMode::Resolve | AppMode::Help => 0,
+            AppMode::Command => self.command_bar.input_cursor_position(),
+            AppMode::Insert => self.insert_bar.input_cursor_position(),
+            AppMode::Search => self.search_bar.input_cursor_position(),
+        }
+    }
+
+    /// Applies `edit` to the task `id`, keeping the old list for undo
+    fn update_task<F>(&mut self, id: &str, edit: F)
+    where
+        F: FnOnce(&mut WorkItem),
+    {
+        let index =
+            match self.tasks.iter().position(|s| s.id.as_deref() == Some(id)) {
+                Some(index) => index,
+                None => return,
+            };
+
+        self.history.record(&self.tasks);
+        edit(&mut self.tasks[index]);
+        self.refresh_view();
+        self.save_project();
+    }
+
+    pub fn update_work_item_text(&mut self, id: &str, content: &str) {
+        self.update_task(id, |task| task.content = Some(content.to_string()));
+    }
+
+    /// Steps back to the task list before the last edit
+    pub fn undo(&mut self) {
+        match self.history.undo(&self.tasks) {
+            Some(tasks) => self.restore_tasks(tasks),
+            None => {
+                self.status_message =
+                    Some("Already at oldest change".to_string())
+            }
+        }
+    }
+
+    /// Steps forward again after `undo`
+    pub fn redo(&mut self) {
+        match self.history.redo(&self.tasks) {
+            Some(tasks) => self.restore_tasks(tasks),
+            None => {
+                self.status_message =
+                    Some("Already at newest change".to_string())
+            }
+        }
+    }
+
+    fn restore_tasks(&mut self, tasks: Vec<WorkItem>) {
+        self.tasks = tasks;
+        self.refresh_view();
+        self.save_project();
+    }
+
+    /// The view in tree order along with the depth of every task
+    pub fn get_tree_view(&self) -> &[(usize, WorkItem)] {
+        &self.view
+    }
+
+    pub fn selected_index(&self) -> usize {
+        self.selected_index
+    }
+
+    pub fn selected_task(&self) -> Option<&WorkItem> {
+        self.view.get(self.selected_index).map(|(_, item)| item)
+    }
+
+    /// Id of the task at `index` in the view
+    fn task_id_at(&self, index: usize) -> Option<String> {
+        self.view.get(index).and_then(|(_, item)| item.id.clone())
+    }
+
+    /// Rebuilds the view after the tasks, the filters or the folds changed
+    /// and finds the selected task in it again. If it is gone, the task that
+    /// took its place is selected.
+    pub fn refresh_view(&mut self) {
+        self.view = self.build_view();
+        let index = self.selected.as_deref().and_then(|id| {
+            self.view
+                .iter()
+                .position(|(_, item)| item.id.as_deref() == Some(id))
+        });
+        match index {
+            Some(index) => self.selected_index = index,
+            None => self.select_index(self.selected_index),
+        }
+    }
+
+    fn build_view(&self) -> Vec<(usize, WorkItem)> {
+        let mut view = self
+            .tasks
+            .iter()
+            .filter(|l| {
+                l.is_valid_for_mode(
+                    self.filter,
+                    self.client_config.show_finished.unwrap(),
+                    self.client_config.show_today.unwrap(),
+                ) && self.tag_filter.matches(l)
+            })
+            .cloned()
+            .collect::<Vec<WorkItem>>();
+
+        // Highest priority first within each status
+        view.sort_by(|a, b| {
+            a.status
+                .partial_cmp(&b.status)
+                .unwrap()
+                .then(b.priority.cmp(&a.priority))
+        });
+        build_tree(&view, &self.collapsed)
+    }
+
+    /// Opens the command bar, `key` is the `:` that started it
+    pub fn start_command(&mut self, key: KeyEvent) {
+        self.mode = AppMode::Command;
+        self.completion = None;
+        self.command_history.reset();
+        self.command_bar.clear();
+        self.command_bar.handle_input(key);
+    }
+
+    /// Passes `key` to the command bar. Tab and Shift-Tab complete the word
+    /// at the end, Up and Down go through previous commands.
+    pub fn handle_command_input(
+        &mut self,
+        key: KeyEvent,
+    ) -> VimCommandBarResult {
+        match key.code {
+            KeyCode::Tab => {
+                let forward = !key.modifiers.contains(KeyModifiers::SHIFT);
+                self.complete_command(forward);
+                return VimCommandBarResult::StillEditing;
+            }
+            KeyCode::Up | KeyCode::Down => {
+                self.completion = None;
+                let current = self.command_bar.buffer().to_string();
+                let entry = if key.code == KeyCode::Up {
+                    self.command_history.previous(&current)
+                } else {
+                    self.command_history.next()
+                };
+                if let Some(entry) = entry.map(String::from) {
+                    self.command_bar.set_buffer(&entry);
+                }
+                return VimCommandBarResult::StillEditing;
+            }
+            _ => {}
+        }
+
+        self.completion = None;
+        let result = self.command_bar.handle_input(key);
+        match &result {
+            VimCommandBarResult::Finished(command) => {
+                let saved = self.command_history.push(command);
+                self.report(saved);
+            }
+            VimCommandBarResult::Aborted => self.command_history.reset(),
+            VimCommandBarResult::StillEditing => {}
+        }
+        result
+    }
+
+    fn complete_command(&mut self, forward: bool) {
+        let mut completion = match self.completion.take() {
+            Some(completion) => completion,
+            None => {
+                let input = self.command_bar.buffer().to_string();
+                let task_count = self.view.len();
+                Completion::new(&input, &self.projects, task_count)
+            }
+        };
+        if let Some(input) = completion.cycle(forward) {
+            self.command_bar.set_buffer(&input);
+        }
+        // A single candidate is filled in without a popup
+        if completion.candidates.len() > 1 {
+            self.completion = Some(completion);
+        }
+    }
+
+    /// Opens the search prompt, `key` is the `/` that started it
+    pub fn start_search(&mut self, key: KeyEvent) {
+        self.mode = AppMode::Search;
+        self.search_origin = self.selected_index;
+        self.search_bar.clear();
+        self.search_bar.handle_input(key);
+    }
+
+    /// Passes `key` to the search prompt and jumps to the first match
+    pub fn handle_search_input(&mut self, key: KeyEvent) {
+        match self.search_bar.handle_input(key) {
+            VimCommandBarResult::StillEditing => {
+                self.update_search();
+                self.select_index(self.search_origin);
+                if self.search.is_some() {
+                    self.search_next(true, true);
+                }
+            }
+            VimCommandBarResult::Finished(input) => {
+                self.mode = AppMode::Global;
+                let pattern = input.trim_start_matches('/');
+                if let Err(e) = Search::new(pattern) {
+                    self.status_message =
+                        Some(format!("Invalid search: {}", e));
+                } else if self.search.is_some()
+                    && !self.view.iter().any(|(_, t)| self.is_search_match(t))
+                {
+                    self.status_message =
+                        Some(format!("Pattern not found: {}", pattern));
+                }
+            }
+            VimCommandBarResult::Aborted => self.abort_search(),
+        }
+    }
+
+    pub fn abort_search(&mut self) {
+        self.search = None;
+        self.search_bar.clear();
+        self.select_index(self.search_origin);
+        self.mode = AppMode::Global;
+    }
+
+    fn update_search(&mut self) {
+        let input = self.search_bar.buffer().to_string();
+        let pattern = input.trim_start_matches('/');
+        // Half typed regexes are usually invalid, only complain on Enter
+        self.search = if pattern.is_empty() {
+            None
+        } else {
+            Search::new(pattern).ok()
+        };
+    }
+
+    pub fn is_search_match(&self, item: &WorkItem) -> bool {
+        self.search
+            .as_ref()
+            .map(|s| s.is_match(item))
+            .unwrap_or(false)
+    }
+
+    /// Selects the next match after the selection, wrapping around.
+    /// `inclusive` also considers the selected task itself.
+    pub fn search_next(&mut self, forward: bool, inclusive: bool) {
+        let len = self.view.len();
+        if len == 0 {
+            return;
+        }
+
+        let start = if inclusive { 0 } else { 1 };
+        for step in start..=len {
+            let index = if forward {
+                (self.selected_index + step) % len
+            } else {
+                (self.selected_index + len * 2 - step) % len
+            };
+            if self.is_search_match(&self.view[index].1) {
+                self.select_index(index);
+                return;
+            }
+        }
+    }
+
+    /// Ids of the children of `id`, their children and so on
+    pub fn descendant_ids(&self, id: &str) -> Vec<String> {
+        let mut ids = vec![id.to_string()];
+        let mut i = 0;
+        while i < ids.len() {
+            for task in &self.tasks {
+                if let (Some(child), Some(parent)) = (&task.id, &task.parent_id)
+                {
+                    if *parent == ids[i] && !ids.contains(child) {
+                        ids.push(child.clone());
+                    }
+                }
+            }
+            i += 1;
+        }
+        ids.remove(0);
+        ids
+    }
+
+    /// Number of closed and total direct children of `id`, if it has any
+    pub fn child_progress(&self, id: &str) -> Option<(usize, usize)> {
+        let children = self
+            .tasks
+            .iter()
+            .filter(|t| t.parent_id.as_deref() == Some(id))
+            .collect::<Vec<&WorkItem>>();
+        if children.is_empty() {
+            return None;
+        }
+
+        let closed = children
+            .iter()
+            .filter(|t| {
+                t.status == ItemStatus::Finished
+                    || t.status == ItemStatus::WontFix
+            })
+            .count();
+        Some((closed, children.len()))
+    }
+
+    pub fn set_collapsed(&mut self, id: &str, collapsed: bool) {
+        if collapsed {
+            if self.child_progress(id).is_some() {
+                self.collapsed.insert(id.to_string());
+            }
+        } else {
+            self.collapsed.remove(id);
+        }
+        self.refresh_view();
+    }
+
+    /// Selects `index`, or the last task if the view is shorter
+    pub fn select_index(&mut self, index: usize) {
+        self.selected_index = index.min(self.view.len().saturating_sub(1));
+        self.selected = self
+            .view
+            .get(self.selected_index)
+            .and_then(|(_, item)| item.id.clone());
+    }
+
+    pub fn open_help(&mut self) {
+        self.help_scroll = 0;
+        self.mode = AppMode::Help;
+    }
+
+    /// Rows of the task table that fit on screen
+    pub fn page_size(&self) -> usize {
+        // Input box, status line, table borders and header
+        usize::from(self.size.height.saturating_sub(7)).max(1)
+    }
+
+    /// Moves the selection to `id` wherever it ended up in the view
+    fn select_task(&mut self, id: &str) {
+        if let Some(index) = self
+            .view
+            .iter()
+            .position(|(_, item)| item.id.as_deref() == Some(id))
         {
-            task.wont_fix()
+            self.selected = Some(id.to_string());
+            self.selected_index = index;
+        }
+    }
+
+    pub fn set_task_notes(&mut self, id: &str, notes: &str) {
+        let notes = notes.trim_end();
+        self.update_task(id, |task| {
+            task.notes = if notes.is_empty() {
+                None
+            } else {
+                Some(notes.to_string())
+            };
+        });
+    }
+
+    pub fn set_task_due(&mut self, id: &str, due: Option<NaiveDate>) {
+        self.update_task(id, |task| task.due = due);
+    }
+
+    pub fn set_task_priority(&mut self, id: &str, priority: usize) {
+        self.update_task(id, |task| task.priority = priority.min(MAX_PRIORITY));
+
+        // Keep the task selected as it moves through the view
+        self.select_task(id);
+    }
+
+    pub fn start_task(&mut self, id: &str) {
+        self.update_task(id, |task| task.start());
+    }
+
+    /// Finishes `id` along with all of its open subtasks
+    pub fn finish_task(&mut self, id: &str) {
+        if !self.tasks.iter().any(|s| s.id.as_deref() == Some(id)) {
+            return;
+        }
+
+        self.history.record(&self.tasks);
+        let children = self.descendant_ids(id);
+
+        for task in self.tasks.iter_mut() {
+            let is_task = task.id.as_deref() == Some(id);
+            let is_open_child = task
+                .id
+                .as_ref()
+                .map(|i| children.contains(i))
+                .unwrap_or(false)
+                && (task.status == ItemStatus::Started
+                    || task.status == ItemStatus::UnStarted);
+
+            if is_task || is_open_child {
+                task.finish();
+            }
+        }
+        self.refresh_view();
+        self.save_project();
+    }
+
+    pub fn wont_task(&mut self, id: &str) {
+        self.update_task(id, |task| task.wont_fix());
+    }
+
+    /// Moves `id` on to the next status, from not started through started,
+    /// finished and won't fix back to not started
+    pub fn cycle_task_status(&mut self, id: &str) {
+        let status =
+            match self.tasks.iter().find(|s| s.id.as_deref() == Some(id)) {
+                Some(task) => task.status,
+                None => return,
+            };
+        match status {
+            ItemStatus::UnStarted => self.start_task(id),
+            ItemStatus::Started => self.finish_task(id),
+            ItemStatus::Finished => self.wont_task(id),
+            ItemStatus::WontFix => self.update_task(id, |task| task.reopen()),
+        }
+        self.select_task(id);
+    }
+
+    /// Handles `key` in the current mode. What the frontend still has to
+    /// do, like quitting, comes back as an `AppAction`.
+    pub async fn handle_key(&mut self, key: KeyEvent) -> AppAction {
+        self.command_error = None;
+        self.status_message = None;
+        if let Some(project) = self.pending_delete.take() {
+            if key.code == KeyCode::Char('y') && key.modifiers.is_empty() {
+                self.delete_project(&project);
+            }
+            return AppAction::Redraw;
+        }
+
+        match key.code {
+            KeyCode::Char('d')
+                if key.modifiers.contains(KeyModifiers::CONTROL)
+                    && !matches!(self.mode, AppMode::Global) =>
+            {
+                return AppAction::Quit;
+            }
+            KeyCode::Esc => {
+                if let AppMode::Search = self.mode {
+                    self.abort_search();
+                }
+                self.key_sequence.clear();
+                self.insert_bar.clear();
+                self.command_bar.clear();
+                self.mode = AppMode::Global;
+                self.select_index(0);
+                return AppAction::HideCursor;
+            }
+            _ => {}
+        }
+
+        match self.mode {
+            AppMode::Global => return self.handle_global_key(key).await,
+            AppMode::Help => self.handle_help_key(key),
+            AppMode::Insert => match self.insert_bar.handle_input(key) {
+                VimCommandBarResult::Finished(task) => {
+                    let mut work_item = WorkItem::from_input(&task);
+                    work_item.parent_id = self.insert_parent.take();
+                    self.add_task(work_item);
+                }
+                VimCommandBarResult::Aborted => self.mode = AppMode::Global,
+                VimCommandBarResult::StillEditing => {}
+            },
+            AppMode::Search => self.handle_search_input(key),
+            AppMode::Resolve => self.handle_resolve_key(key),
+            AppMode::Command => match self.handle_command_input(key) {
+                VimCommandBarResult::Finished(input) => {
+                    self.mode = AppMode::Global;
+                    match input.parse::<VimCommand>() {
+                        Ok(command) => return self.run_command(command).await,
+                        Err(e) => self.command_error = Some(e.to_string()),
+                    }
+                }
+                VimCommandBarResult::Aborted => self.mode = AppMode::Global,
+                VimCommandBarResult::StillEditing => {}
+            },
+        }
+        AppAction::Redraw
+    }
+
+    async fn handle_global_key(&mut self, key: KeyEvent) -> AppAction {
+        let keymap = &self.keymap;
+        let (count, keys) =
+            match self.key_sequence.push(key, |keys| keymap.is_prefix(keys)) {
+                Some(sequence) => sequence,
+                None => return AppAction::Redraw,
+            };
+        let action = match self.keymap.get(&keys) {
+            Some(action) => action,
+            None => return AppAction::Redraw,
+        };
+
+        let selected = self.task_id_at(self.selected_index);
+
+        match action {
+            Action::StartTask => {
+                if let Some(id) = selected {
+                    self.start_task(&id)
+                }
+            }
+            Action::FinishTask => {
+                if let Some(id) = selected {
+                    self.finish_task(&id)
+                }
+            }
+            Action::WontFix => {
+                if let Some(id) = selected {
+                    self.wont_task(&id)
+                }
+            }
+            Action::Delete => {
+                let ids = self
+                    .view
+                    .iter()
+                    .skip(self.selected_index)
+                    .take(count.unwrap_or(1))
+                    .filter_map(|(_, w)| w.id.clone())
+                    .collect::<Vec<String>>();
+                self.remove_tasks(&ids);
+            }
+            Action::Paste => self.paste_register(),
+            Action::Refresh => self.sync().await,
+            Action::FixIds => self.fix_all_work_items(),
+            Action::Insert => {
+                self.insert_parent = None;
+                self.mode = AppMode::Insert;
+            }
+            Action::AddSubtask => {
+                if selected.is_some() {
+                    self.insert_parent = selected;
+                    self.mode = AppMode::Insert;
+                }
+            }
+            Action::Command => self.start_command(KeyCode::Char(':').into()),
+            Action::Search => self.start_search(KeyCode::Char('/').into()),
+            Action::SearchNext => {
+                for _ in 0..count.unwrap_or(1) {
+                    self.search_next(true, false);
+                }
+            }
+            Action::SearchPrev => {
+                for _ in 0..count.unwrap_or(1) {
+                    self.search_next(false, false);
+                }
+            }
+            Action::Up => {
+                for _ in 0..count.unwrap_or(1) {
+                    self.select_index(on_up_press_handler(
+                        &self.view,
+                        Some(self.selected_index),
+                    ));
+                }
+            }
+            Action::Down => {
+                for _ in 0..count.unwrap_or(1) {
+                    self.select_index(on_down_press_handler(
+                        &self.view,
+                        Some(self.selected_index),
+                    ));
+                }
+            }
+            Action::Top => self.select_index(count.unwrap_or(0)),
+            Action::Bottom => self.select_index(count.unwrap_or(usize::MAX)),
+            Action::HalfPageUp => {
+                let page = self.page_size() / 2;
+                self.select_index(self.selected_index.saturating_sub(page));
+            }
+            Action::HalfPageDown => {
+                let page = self.page_size() / 2;
+                self.select_index(self.selected_index + page);
+            }
+            Action::PageUp => {
+                let page = self.page_size();
+                self.select_index(self.selected_index.saturating_sub(page));
+            }
+            Action::PageDown => {
+                let page = self.page_size();
+                self.select_index(self.selected_index + page);
+            }
+            Action::PriorityUp => {
+                if let Some(id) = selected {
+                    let priority =
+                        self.selected_task().map_or(0, |w| w.priority);
+                    self.set_task_priority(&id, priority + 1)
+                }
+            }
+            Action::PriorityDown => {
+                if let Some(id) = selected {
+                    let priority =
+                        self.selected_task().map_or(0, |w| w.priority);
+                    self.set_task_priority(&id, priority.saturating_sub(1))
+                }
+            }
+            Action::Collapse => {
+                if let Some(id) = selected {
+                    self.set_collapsed(&id, true);
+                }
+            }
+            Action::Expand => {
+                if let Some(id) = selected {
+                    self.set_collapsed(&id, false);
+                }
+            }
+            Action::ToggleDetails => self.show_details = !self.show_details,
+            Action::EditNotes => {
+                if let Some(id) = selected {
+                    return AppAction::EditNotes {
+                        id,
+                        notes: self
+                            .selected_task()
+                            .and_then(|w| w.notes.clone())
+                            .unwrap_or_default(),
+                    };
+                }
+            }
+            Action::Undo => self.undo(),
+            Action::Redo => self.redo(),
+            Action::Clear => {
+                self.insert_bar.clear();
+                self.command_bar.clear();
+                self.select_index(0);
+                return AppAction::HideCursor;
+            }
+            Action::Help => self.open_help(),
+        }
+        AppAction::Redraw
+    }
+
+    fn handle_help_key(&mut self, key: KeyEvent) {
+        let max_scroll = self.help_max_scroll.get();
+        match key.code {
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.help_scroll = (self.help_scroll + 1).min(max_scroll)
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.help_scroll = self.help_scroll.saturating_sub(1)
+            }
+            KeyCode::PageDown => {
+                self.help_scroll =
+                    (self.help_scroll + self.page_size()).min(max_scroll)
+            }
+            KeyCode::PageUp => {
+                self.help_scroll =
+                    self.help_scroll.saturating_sub(self.page_size())
+            }
+            KeyCode::Char('g') | KeyCode::Home => self.help_scroll = 0,
+            KeyCode::Char('q') | KeyCode::Char('?') => {
+                self.mode = AppMode::Global
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_resolve_key(&mut self, key: KeyEvent) {
+        if let KeyCode::Enter = key.code {
+            self.apply_resolution();
+            return;
+        }
+
+        let resolution = match self.resolution.as_mut() {
+            Some(resolution) => resolution,
+            None => return,
+        };
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => {
+                resolution.selected_index = on_up_press_handler(
+                    &resolution.choices,
+                    Some(resolution.selected_index),
+                );
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                resolution.selected_index = on_down_press_handler(
+                    &resolution.choices,
+                    Some(resolution.selected_index),
+                );
+            }
+            KeyCode::Char('l') => resolution.choose(MergeSide::Local),
+            KeyCode::Char('r') => resolution.choose(MergeSide::Remote),
+            KeyCode::Char('L') => resolution.choose_all(MergeSide::Local),
+            KeyCode::Char('R') => resolution.choose_all(MergeSide::Remote),
+            _ => {}
         }
     }
 
-    pub fn add_task(&mut self, mut item: WorkItem) {
-        item.id = Some(Uuid::new_v4().to_string());
+    /// Runs a `:` command, task indexes refer to the current view
+    pub async fn run_command(&mut self, command: VimCommand) -> AppAction {
+        match command {
+            VimCommand::Quit => return AppAction::Quit,
+            VimCommand::ProjectSaveAndQuit => {
+                self.save_project_and_push().await;
+                return AppAction::Quit;
+            }
+            VimCommand::ProjectSave => self.save_project(),
+            VimCommand::TaskRename(index, content) => {
+                if let Some(id) = self.task_id_at(index) {
+                    self.update_work_item_text(&id, &content);
+                }
+            }
+            VimCommand::TaskDelete(index) => {
+                if let Some(id) = self.task_id_at(index) {
+                    self.remove_task(&id)
+                }
+            }
+            VimCommand::ProjectNew(name) => self.new_project(&name),
+            VimCommand::ProjectDelete(name) => self.pending_delete = Some(name),
+            VimCommand::ProjectOpen(name) => self.select_project(&name),
+            VimCommand::ShowFinished(value) => {
+                self.client_config.show_finished = Some(value);
+                let _ = self.client_config.save_config();
+                self.refresh_view();
+            }
+            VimCommand::ShowToday(value) => {
+                self.client_config.show_today = Some(value);
+                let _ = self.client_config.save_config();
+                self.refresh_view();
+            }
+            VimCommand::Filter(filter) => {
+                self.tag_filter = filter;
+                self.refresh_view();
+            }
+            VimCommand::Resolve => self.open_resolution(),
+            VimCommand::Help => self.open_help(),
+            VimCommand::TaskSetDue(index, value) => {
+                let today = Local::now().date_naive();
+                match parse_due_date(&value, today) {
+                    Ok(due) => {
+                        if let Some(id) = self.task_id_at(index) {
+                            self.set_task_due(&id, due);
+                        }
+                    }
+                    Err(e) => self.status_message = Some(e),
+                }
+            }
+            VimCommand::TaskSetPriority(index, value) => {
+                if let Some(id) = self.task_id_at(index) {
+                    self.set_task_priority(&id, value);
+                }
+            }
+            VimCommand::None => {}
+        }
+        AppAction::Redraw
+    }
+
+    /// A click selects a task, a double click also shows its details and a
+    /// click on its status moves it on to the next one. The wheel moves the
+    /// selection.
+    pub fn handle_mouse(&mut self, event: MouseEvent) {
+        if !matches!(self.mode, AppMode::Global) {
+            return;
+        }
+
+        match event.kind {
+            MouseEventKind::ScrollDown => {
+                self.select_index(self.selected_index + 1)
+            }
+            MouseEventKind::ScrollUp => {
+                self.select_index(self.selected_index.saturating_sub(1))
+            }
+            MouseEventKind::Down(MouseButton::Left) => {
+                let layout = self.table_layout.get();
+                let (index, id) = match layout
+                    .task_at(event.column, event.row)
+                    .and_then(|i| Some((i, self.view.get(i)?.1.id.clone())))
+                {
+                    Some(clicked) => clicked,
+                    None => return,
+                };
+                self.select_index(index);
+
+                if layout.is_status(event.column) {
+                    self.last_click = None;
+                    if let Some(id) = id {
+                        self.cycle_task_status(&id);
+                    }
+                    return;
+                }
+
+                let now = Instant::now();
+                match self.last_click {
+                    Some((time, last))
+                        if last == index
+                            && now - time < DOUBLE_CLICK_INTERVAL =>
+                    {
+                        self.show_details = true;
+                        self.last_click = None;
+                    }
+                    _ => self.last_click = Some((now, index)),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Adds `item` with a fresh id and returns it
+    pub fn add_task(&mut self, mut item: WorkItem) -> String {
+        let id = Uuid::new_v4().to_string();
+        item.id = Some(id.clone());
+        if let Some(parent) = &item.parent_id {
+            self.collapsed.remove(parent);
+        }
+        self.history.record(&self.tasks);
         self.tasks.push(item);
         self.tasks
             .sort_by(|a, b| a.status.partial_cmp(&b.status).unwrap());
-        self.selected_index = self.tasks.len() - 1;
+        self.refresh_view();
+        self.select_task(&id);
         self.mode = AppMode::Global;
         self.insert_bar.clear();
-        self.save_project(false, false);
+        self.save_project();
+        id
     }
 
+    /// Removes `id` and all of its subtasks, keeping them in the register
     pub fn remove_task(&mut self, id: &str) {
-        if let Some(task) = self
-            .tasks
-            .iter_mut()
-            .position(|s| s.id == Some(id.to_string()))
+        self.remove_tasks(&[id.to_string()]);
+    }
+
+    /// Removes all of `ids` and their subtasks as a single edit
+    pub fn remove_tasks(&mut self, ids: &[String]) {
+        let mut removed = Vec::new();
+        for id in ids {
+            if !removed.contains(id)
+                && self.tasks.iter().any(|s| s.id.as_ref() == Some(id))
+            {
+                removed.push(id.clone());
+                removed.extend(self.descendant_ids(id));
+            }
+        }
+        if removed.is_empty() {
+            return;
+        }
+
+        self.history.record(&self.tasks);
+        self.register = removed
+            .iter()
+            .filter_map(|i| {
+                self.tasks
+                    .iter()
+                    .find(|s| s.id.as_ref() == Some(i))
+                    .cloned()
+            })
+            .collect();
+        self.tasks.retain(|s| {
+            s.id.as_ref().map(|i| !removed.contains(i)).unwrap_or(true)
+        });
+        self.refresh_view();
+        self.save_project();
+    }
+
+    /// Adds back the tasks last removed, under new ids
+    pub fn paste_register(&mut self) {
+        if self.register.is_empty() {
+            return;
+        }
+
+        self.history.record(&self.tasks);
+        let mut new_ids = HashMap::new();
+        for item in &self.register {
+            if let Some(id) = &item.id {
+                new_ids.insert(id.clone(), Uuid::new_v4().to_string());
+            }
+        }
+
+        for mut item in self.register.clone() {
+            item.id = item.id.and_then(|id| new_ids.get(&id).cloned());
+            if let Some(parent) =
+                item.parent_id.as_ref().and_then(|p| new_ids.get(p))
+            {
+                item.parent_id = Some(parent.clone());
+            }
+            self.tasks.push(item);
+        }
+        self.refresh_view();
+
+        if let Some(id) = self
+            .register
+            .first()
+            .and_then(|item| item.id.as_ref())
+            .and_then(|id| new_ids.get(id))
+            .cloned()
         {
-            self.register = Some(self.tasks[task].clone());
-            self.tasks.remove(task);
+            self.select_task(&id);
         }
+        self.save_project();
     }
 
     pub fn fix_all_work_items(&mut self) {
+        self.history.record(&self.tasks);
+        let mut new_ids = HashMap::new();
         for x in self.tasks.iter_mut() {
-            x.id = Some(Uuid::new_v4().to_string());
+            let id = Uuid::new_v4().to_string();
+            if let Some(old) = x.id.replace(id.clone()) {
+                new_ids.insert(old, id);
+            }
+        }
+
+        // Keep subtasks attached to their parents
+        for x in self.tasks.iter_mut() {
+            x.parent_id =
+                x.parent_id.take().and_then(|p| new_ids.get(&p).cloned());
+        }
+        self.refresh_view();
+        self.save_project();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn due_dates_relative_to_today() {
+        // A Wednesday
+        let today = date(2026, 10, 14);
+        let due = |input| parse_due_date(input, today);
+
+        assert_eq!(due("today"), Ok(Some(today)));
+        assert_eq!(due("tomorrow"), Ok(Some(date(2026, 10, 15))));
+        assert_eq!(due("fri"), Ok(Some(date(2026, 10, 16))));
+        assert_eq!(due("wed"), Ok(Some(date(2026, 10, 21))));
+        assert_eq!(due("+3d"), Ok(Some(date(2026, 10, 17))));
+        assert_eq!(due("-1w"), Ok(Some(date(2026, 10, 7))));
+        assert_eq!(due("2026-11-03"), Ok(Some(date(2026, 11, 3))));
+        assert_eq!(due("none"), Ok(None));
+        assert_eq!(due("-"), Ok(None));
+    }
+
+    #[test]
+    fn bad_due_dates_are_errors() {
+        let today = date(2026, 10, 14);
+        let due = |input| parse_due_date(input, today);
+
+        assert!(due("+3é").is_err());
+        assert!(due("+").is_err());
+        assert!(due("+d").is_err());
+        assert!(due("+3y").is_err());
+        assert!(due("someday").is_err());
+        assert_eq!(
+            due("+99999999999d"),
+            Err(String::from("due date out of range"))
+        );
+        assert_eq!(
+            due("-99999999999999w"),
+            Err(String::from("due date out of range"))
+        );
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        code.into()
+    }
+
+    fn ctrl(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL)
+    }
+
+    fn alt(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::ALT)
+    }
+
+    fn typed(text: &str) -> Vec<KeyEvent> {
+        text.chars().map(|c| KeyCode::Char(c).into()).collect()
+    }
+
+    /// Feeds `keys` to a new bar, returns its text and cursor column
+    fn edit(keys: Vec<KeyEvent>) -> (String, u16) {
+        let mut bar = VimBar::new();
+        for key in keys {
+            bar.handle_input(key);
+        }
+        (bar.buffer().to_string(), bar.input_cursor_position())
+    }
+
+    #[test]
+    fn inserts_at_the_cursor() {
+        let mut keys = typed("helo");
+        keys.push(key(KeyCode::Left));
+        keys.extend(typed("l"));
+        assert_eq!(edit(keys), ("hello".to_string(), 4));
+    }
+
+    #[test]
+    fn moves_left_and_right_within_the_line() {
+        let mut keys = typed("ab");
+        keys.extend([key(KeyCode::Left), key(KeyCode::Left)]);
+        keys.push(key(KeyCode::Left));
+        keys.extend(typed("x"));
+        keys.extend([key(KeyCode::Right), key(KeyCode::Right)]);
+        keys.push(key(KeyCode::Right));
+        keys.extend(typed("y"));
+        assert_eq!(edit(keys), ("xaby".to_string(), 4));
+    }
+
+    #[test]
+    fn home_end_and_ctrl_a_ctrl_e() {
+        let mut keys = typed("bc");
+        keys.push(key(KeyCode::Home));
+        keys.extend(typed("a"));
+        keys.push(key(KeyCode::End));
+        keys.extend(typed("d"));
+        keys.push(ctrl('a'));
+        keys.extend(typed(">"));
+        keys.push(ctrl('e'));
+        keys.extend(typed("<"));
+        assert_eq!(edit(keys), (">abcd<".to_string(), 6));
+    }
+
+    #[test]
+    fn control_keys_are_not_inserted() {
+        let mut keys = typed("ab");
+        keys.extend([ctrl('a'), ctrl('u'), alt('x'), ctrl('z')]);
+        assert_eq!(edit(keys), ("ab".to_string(), 0));
+    }
+
+    #[test]
+    fn word_motions() {
+        let mut keys = typed("tmod 3 new_name");
+        keys.push(alt('b'));
+        keys.extend(typed("["));
+        keys.push(alt('b'));
+        keys.extend(typed("<"));
+        keys.push(alt('f'));
+        keys.extend(typed(">"));
+        assert_eq!(edit(keys), ("tmod <3> [new_name".to_string(), 8));
+    }
+
+    #[test]
+    fn backspace_and_delete_keep_the_cursor_in_place() {
+        let mut keys = typed("abcd");
+        keys.extend([key(KeyCode::Left), key(KeyCode::Left)]);
+        keys.push(key(KeyCode::Delete));
+        keys.push(key(KeyCode::Backspace));
+        assert_eq!(edit(keys), ("ad".to_string(), 1));
+    }
+
+    #[test]
+    fn delete_at_the_end_does_nothing() {
+        let mut keys = typed("ab");
+        keys.push(key(KeyCode::Delete));
+        assert_eq!(edit(keys), ("ab".to_string(), 2));
+    }
+
+    #[test]
+    fn ctrl_w_deletes_the_previous_word() {
+        let mut keys = typed("o my-project  ");
+        keys.push(ctrl('w'));
+        assert_eq!(edit(keys), ("o ".to_string(), 2));
+    }
+
+    #[test]
+    fn ctrl_k_kills_to_the_end_and_ctrl_y_yanks_it_back() {
+        let mut keys = typed("hello world");
+        keys.extend(std::iter::repeat_n(key(KeyCode::Left), 6));
+        keys.push(ctrl('k'));
+        keys.push(key(KeyCode::Home));
+        keys.push(ctrl('y'));
+        assert_eq!(edit(keys), (" worldhello".to_string(), 6));
+    }
+
+    #[test]
+    fn ctrl_u_kills_to_the_start() {
+        let mut keys = typed("hello world");
+        keys.push(alt('b'));
+        keys.push(ctrl('u'));
+        assert_eq!(edit(keys), ("world".to_string(), 0));
+    }
+
+    #[test]
+    fn cursor_counts_wide_characters_twice() {
+        let mut keys = typed("日本語");
+        keys.push(key(KeyCode::Left));
+        assert_eq!(edit(keys), ("日本語".to_string(), 4));
+    }
+
+    fn history(commands: &[&str]) -> CommandHistory {
+        let mut history = CommandHistory::default();
+        for command in commands {
+            history.push(command).unwrap();
+        }
+        history
+    }
+
+    #[test]
+    fn history_moves_repeated_commands_to_the_end() {
+        let mut history = history(&[":o work", ":w", ":o work", "  "]);
+        assert_eq!(history.entries, vec![":w", ":o work"]);
+        assert_eq!(history.previous(""), Some(":o work"));
+        assert_eq!(history.previous(""), Some(":w"));
+    }
+
+    #[test]
+    fn history_keeps_the_latest_commands() {
+        let commands = (0..COMMAND_HISTORY_LIMIT + 10)
+            .map(|i| format!(":tdel {}", i))
+            .collect::<Vec<String>>();
+        let history =
+            history(&commands.iter().map(String::as_str).collect::<Vec<_>>());
+        assert_eq!(history.entries.len(), COMMAND_HISTORY_LIMIT);
+        assert_eq!(history.entries.first(), Some(&commands[10]));
+        assert_eq!(history.entries.last(), commands.last());
+    }
+
+    #[test]
+    fn history_stops_at_both_ends() {
+        let mut history = history(&[":w", ":q"]);
+        assert_eq!(history.next(), None);
+
+        assert_eq!(history.previous(":o"), Some(":q"));
+        assert_eq!(history.previous(":o"), Some(":w"));
+        assert_eq!(history.previous(":o"), Some(":w"));
+        assert_eq!(history.next(), Some(":q"));
+        // Past the newest entry is what was being typed
+        assert_eq!(history.next(), Some(":o"));
+        assert_eq!(history.next(), None);
+
+        assert_eq!(CommandHistory::default().previous(":o"), None);
+    }
+
+    #[test]
+    fn enter_finishes_and_backspace_on_empty_aborts() {
+        let mut bar = VimBar::new();
+        for key in typed(":w") {
+            bar.handle_input(key);
         }
+        assert!(matches!(
+            bar.handle_input(key(KeyCode::Enter)),
+            VimCommandBarResult::Finished(input) if input == ":w"
+        ));
+        assert_eq!(bar.buffer().len_chars(), 0);
+        assert!(matches!(
+            bar.handle_input(key(KeyCode::Backspace)),
+            VimCommandBarResult::Aborted
+        ));
     }
 }
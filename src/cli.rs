@@ -0,0 +1,222 @@
+use super::app::{App, ItemStatus, WorkItem};
+use anyhow::anyhow;
+use anyhow::Result;
+use clap::{App as ClapApp, AppSettings, Arg, ArgMatches, SubCommand};
+
+fn project_arg() -> Arg<'static, 'static> {
+    Arg::with_name("project")
+        .long("project")
+        .short("p")
+        .takes_value(true)
+        .help("Project to use instead of the current one")
+}
+
+fn id_arg() -> Arg<'static, 'static> {
+    Arg::with_name("id")
+        .required(true)
+        .help("Id of the task, or any unique prefix of it")
+}
+
+pub fn subcommands() -> Vec<ClapApp<'static, 'static>> {
+    vec![
+        SubCommand::with_name("add")
+            .about("Adds a task")
+            .setting(AppSettings::TrailingVarArg)
+            .arg(project_arg())
+            .arg(
+                Arg::with_name("parent")
+                    .long("parent")
+                    .takes_value(true)
+                    .help("Id of the task to add this one under"),
+            )
+            .arg(
+                Arg::with_name("text")
+                    .required(true)
+                    .multiple(true)
+                    .help("Content of the task"),
+            ),
+        SubCommand::with_name("list")
+            .about("Lists the tasks of a project")
+            .arg(project_arg())
+            .arg(
+                Arg::with_name("status")
+                    .long("status")
+                    .short("s")
+                    .takes_value(true)
+                    .possible_values(&[
+                        "started",
+                        "finished",
+                        "done",
+                        "unstarted",
+                        "todo",
+                        "wontfix",
+                        "wont",
+                    ])
+                    .help("Only list tasks with this status"),
+            )
+            .arg(
+                Arg::with_name("json")
+                    .long("json")
+                    .help("Print the tasks as JSON"),
+            ),
+        SubCommand::with_name("start")
+            .about("Starts a task")
+            .arg(project_arg())
+            .arg(id_arg()),
+        SubCommand::with_name("done")
+            .about("Finishes a task")
+            .arg(project_arg())
+            .arg(id_arg()),
+        SubCommand::with_name("wont")
+            .about("Marks a task as won't fix")
+            .arg(project_arg())
+            .arg(id_arg()),
+        SubCommand::with_name("rm")
+            .about("Removes a task")
+            .arg(project_arg())
+            .arg(id_arg()),
+        SubCommand::with_name("projects").about("Lists all projects"),
+    ]
+}
+
+/// Id of the single task whose id starts with `prefix`
+fn find_task_id(app: &App, prefix: &str) -> Result<String> {
+    let matches = app
+        .tasks
+        .iter()
+        .filter_map(|t| t.id.as_ref())
+        .filter(|id| id.starts_with(prefix))
+        .collect::<Vec<&String>>();
+
+    match matches.as_slice() {
+        [id] => Ok(id.to_string()),
+        [] => Err(anyhow!("no task matches id {}", prefix)),
+        _ => Err(anyhow!("id {} matches {} tasks", prefix, matches.len())),
+    }
+}
+
+/// Runs `edit` and fails with whatever kept its result from being saved
+fn edit<T>(app: &mut App, edit: impl FnOnce(&mut App) -> T) -> Result<T> {
+    app.status_message = None;
+    let value = edit(app);
+    match app.status_message.take() {
+        Some(error) => Err(anyhow!(error)),
+        None => Ok(value),
+    }
+}
+
+fn print_task(task: &WorkItem) {
+    let id = task.id.as_deref().unwrap_or("-");
+    println!(
+        "{:<8}  {:<9}  {}",
+        id.chars().take(8).collect::<String>(),
+        task.status.to_string(),
+        task.content.as_deref().unwrap_or("")
+    );
+}
+
+/// Runs the subcommand in `matches` against the tasks of `app`
+pub async fn run(mut app: App, matches: &ArgMatches<'_>) -> Result<()> {
+    app.load();
+    app.sync().await;
+    if let Some(error) = app.status_line() {
+        eprintln!("warning: {}, using cached tasks", error);
+    }
+
+    let (name, args) = match matches.subcommand() {
+        (name, Some(args)) => (name, args),
+        _ => return Ok(()),
+    };
+
+    if let Some(project) = args.value_of("project") {
+        if !app.get_projects().iter().any(|p| p == project) {
+            return Err(anyhow!("project {} does not exist", project));
+        }
+        app.open_project(project);
+    }
+
+    let edits = matches!(name, "add" | "start" | "done" | "wont" | "rm");
+    if edits && app.current_project.is_none() {
+        return Err(anyhow!("no project selected"));
+    }
+
+    match name {
+        "add" => {
+            let text = args
+                .values_of("text")
+                .map(|v| v.collect::<Vec<&str>>().join(" "))
+                .unwrap_or_default();
+
+            let mut work_item = WorkItem::from_input(&text);
+            if let Some(parent) = args.value_of("parent") {
+                work_item.parent_id = Some(find_task_id(&app, parent)?);
+            }
+            let id = edit(&mut app, |app| app.add_task(work_item))?;
+            println!("{}", id);
+        }
+        "list" => {
+            let status = match args.value_of("status") {
+                Some(status) => {
+                    Some(status.parse::<ItemStatus>().map_err(|e| anyhow!(e))?)
+                }
+                None => None,
+            };
+
+            let mut tasks = app
+                .tasks
+                .iter()
+                .filter(|t| status.map(|s| t.status == s).unwrap_or(true))
+                .cloned()
+                .collect::<Vec<WorkItem>>();
+            tasks.sort_by(|a, b| {
+                a.status
+                    .partial_cmp(&b.status)
+                    .unwrap()
+                    .then(b.priority.cmp(&a.priority))
+            });
+
+            if args.is_present("json") {
+                println!("{}", serde_json::to_string_pretty(&tasks)?);
+            } else {
+                tasks.iter().for_each(print_task);
+            }
+        }
+        "start" | "done" | "wont" | "rm" => {
+            let id = find_task_id(&app, args.value_of("id").unwrap_or(""))?;
+            edit(&mut app, |app| match name {
+                "start" => app.start_task(&id),
+                "done" => app.finish_task(&id),
+                "wont" => app.wont_task(&id),
+                _ => app.remove_task(&id),
+            })?;
+        }
+        "projects" => {
+            for project in app.get_projects() {
+                let marker = if app.current_project.as_ref() == Some(&project) {
+                    "*"
+                } else {
+                    " "
+                };
+                println!("{} {}", marker, project);
+            }
+        }
+        _ => {}
+    }
+
+    // Edits are queued in the cache, upload them before exiting
+    if app.sync_queue.push().await.is_err() {
+        if let Some(error) = app.status_line() {
+            eprintln!("warning: {}, changes will be uploaded later", error);
+        }
+    }
+
+    let conflicts = app.sync_queue.conflicted_projects();
+    if !conflicts.is_empty() {
+        return Err(anyhow!(
+            "not uploaded because of conflicts: {}, run :resolve in bc",
+            conflicts.join(", ")
+        ));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,602 @@
+use super::app::{TagFilter, MAX_PRIORITY};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(PartialEq, Eq, Clone, Hash, Debug)]
+pub enum VimCommand {
+    TaskRename(usize, String),
+    TaskDelete(usize),
+    TaskSetPriority(usize, usize),
+    TaskSetDue(usize, String),
+    ProjectNew(String),
+    ProjectOpen(String),
+    ProjectDelete(String),
+    ProjectSave,
+    ProjectSaveAndQuit,
+    ShowToday(bool),
+    ShowFinished(bool),
+    Filter(TagFilter),
+    Resolve,
+    Help,
+    Quit,
+    None,
+}
+
+/// A `:` command as listed in the help
+pub struct CommandHelp {
+    pub name: &'static str,
+    /// Longer names that run the same command
+    pub aliases: &'static [&'static str],
+    pub args: &'static str,
+    pub help: &'static str,
+}
+
+/// Every command `VimCommand` parses
+pub const COMMANDS: [CommandHelp; 15] = [
+    CommandHelp {
+        name: "q",
+        aliases: &["quit"],
+        args: "",
+        help: "Quit",
+    },
+    CommandHelp {
+        name: "w",
+        aliases: &["write"],
+        args: "",
+        help: "Upload the project now",
+    },
+    CommandHelp {
+        name: "wq",
+        aliases: &[],
+        args: "",
+        help: "Upload the project and quit",
+    },
+    CommandHelp {
+        name: "resolve",
+        aliases: &[],
+        args: "",
+        help: "Resolve sync conflicts of the project",
+    },
+    CommandHelp {
+        name: "tmod",
+        aliases: &["rename"],
+        args: "<index> <text>",
+        help: "Rename a task",
+    },
+    CommandHelp {
+        name: "tdel",
+        aliases: &["delete"],
+        args: "<index>",
+        help: "Delete a task",
+    },
+    CommandHelp {
+        name: "tp",
+        aliases: &["priority"],
+        args: "<index> <priority>",
+        help: "Set the priority of a task, 0 to 3",
+    },
+    CommandHelp {
+        name: "due",
+        aliases: &[],
+        args: "<index> <date>",
+        help: "Set a due date: 2026-11-03, tomorrow, fri, +3d or none",
+    },
+    CommandHelp {
+        name: "o",
+        aliases: &["open"],
+        args: "<project>",
+        help: "Open a project",
+    },
+    CommandHelp {
+        name: "n",
+        aliases: &["new"],
+        args: "<project>",
+        help: "Create a project",
+    },
+    CommandHelp {
+        name: "pdel",
+        aliases: &[],
+        args: "<project>",
+        help: "Delete a project after asking",
+    },
+    CommandHelp {
+        name: "sf",
+        aliases: &[],
+        args: "<true|false>",
+        help: "Show finished tasks",
+    },
+    CommandHelp {
+        name: "st",
+        aliases: &[],
+        args: "<true|false>",
+        help: "Only show tasks due today, overdue or started today",
+    },
+    CommandHelp {
+        name: "filter",
+        aliases: &[],
+        args: "[+tag] [-+tag]",
+        help: "Only show tasks with or without tags, empty to clear",
+    },
+    CommandHelp {
+        name: "help",
+        aliases: &[],
+        args: "",
+        help: "Show this help",
+    },
+];
+
+/// Why a `:` command could not be run
+#[derive(Debug, PartialEq, Eq)]
+pub enum CommandError {
+    Unknown(String),
+    /// An argument is missing, `expected` describes it
+    Missing {
+        command: String,
+        expected: &'static str,
+    },
+    Invalid {
+        command: String,
+        expected: &'static str,
+        value: String,
+    },
+    TooManyArguments(String),
+    UnterminatedQuote,
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommandError::Unknown(name) => {
+                write!(f, "Not a command: {}", name)
+            }
+            CommandError::Missing { command, expected } => {
+                write!(f, "{}: expected {}", command, expected)
+            }
+            CommandError::Invalid {
+                command,
+                expected,
+                value,
+            } => write!(f, "{}: expected {}, got {}", command, expected, value),
+            CommandError::TooManyArguments(command) => {
+                write!(f, "{}: too many arguments", command)
+            }
+            CommandError::UnterminatedQuote => write!(f, "Unterminated quote"),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// Splits on whitespace, except inside `"` or `'` quotes. A backslash keeps
+/// the next character as is.
+fn tokenize(input: &str) -> Result<Vec<String>, CommandError> {
+    let mut tokens = Vec::new();
+    let mut token: Option<String> = None;
+    let mut quote = None;
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (_, '\\') => {
+                let escaped = chars.next().unwrap_or('\\');
+                token.get_or_insert_with(String::new).push(escaped);
+            }
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => token.get_or_insert_with(String::new).push(c),
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                token.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => tokens.extend(token.take()),
+            (None, c) => token.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if quote.is_some() {
+        return Err(CommandError::UnterminatedQuote);
+    }
+    tokens.extend(token);
+    Ok(tokens)
+}
+
+/// Arguments of a single command, consumed in order
+struct Args {
+    command: String,
+    tokens: std::vec::IntoIter<String>,
+}
+
+impl Args {
+    fn next(&mut self, expected: &'static str) -> Result<String, CommandError> {
+        self.tokens.next().ok_or_else(|| CommandError::Missing {
+            command: self.command.clone(),
+            expected,
+        })
+    }
+
+    fn parse<T: FromStr>(
+        &mut self,
+        expected: &'static str,
+    ) -> Result<T, CommandError> {
+        let value = self.next(expected)?;
+        value.parse::<T>().map_err(|_| CommandError::Invalid {
+            command: self.command.clone(),
+            expected,
+            value,
+        })
+    }
+
+    /// All remaining arguments joined by spaces
+    fn rest(&mut self, expected: &'static str) -> Result<String, CommandError> {
+        let rest = self.tokens.by_ref().collect::<Vec<String>>().join(" ");
+        if rest.is_empty() {
+            return Err(CommandError::Missing {
+                command: self.command.clone(),
+                expected,
+            });
+        }
+        Ok(rest)
+    }
+
+    fn done(&mut self) -> Result<(), CommandError> {
+        match self.tokens.next() {
+            Some(_) => {
+                Err(CommandError::TooManyArguments(self.command.clone()))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl FromStr for VimCommand {
+    type Err = CommandError;
+
+    /// Parses command bar input like `:tmod 3 "new name"`
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.strip_prefix(':').unwrap_or(input);
+        let mut tokens = tokenize(input)?.into_iter();
+        let name = match tokens.next() {
+            Some(name) => name,
+            None => return Ok(VimCommand::None),
+        };
+
+        let command = find_command(&name)
+            .ok_or_else(|| CommandError::Unknown(name.clone()))?;
+
+        let mut args = Args {
+            command: name,
+            tokens,
+        };
+
+        let parsed = match command.name {
+            "q" => VimCommand::Quit,
+            "w" => VimCommand::ProjectSave,
+            "wq" => VimCommand::ProjectSaveAndQuit,
+            "resolve" => VimCommand::Resolve,
+            "help" => VimCommand::Help,
+            "tmod" => {
+                let index = args.parse("task index")?;
+                VimCommand::TaskRename(index, args.rest("task text")?)
+            }
+            "tdel" => VimCommand::TaskDelete(args.parse("task index")?),
+            "tp" => {
+                let index = args.parse("task index")?;
+                let priority = args.parse::<usize>("priority 0 to 3")?;
+                if priority > MAX_PRIORITY {
+                    return Err(CommandError::Invalid {
+                        command: args.command,
+                        expected: "priority 0 to 3",
+                        value: priority.to_string(),
+                    });
+                }
+                VimCommand::TaskSetPriority(index, priority)
+            }
+            "due" => {
+                let index = args.parse("task index")?;
+                let due = args.next("due date")?;
+                VimCommand::TaskSetDue(index, due)
+            }
+            "o" => VimCommand::ProjectOpen(args.next("project name")?),
+            "n" => VimCommand::ProjectNew(args.next("project name")?),
+            "pdel" => VimCommand::ProjectDelete(args.next("project name")?),
+            "sf" => VimCommand::ShowFinished(args.parse("true or false")?),
+            "st" => VimCommand::ShowToday(args.parse("true or false")?),
+            "filter" => {
+                let tags = args.tokens.by_ref().collect::<Vec<String>>();
+                VimCommand::Filter(TagFilter::from_tokens(
+                    tags.iter().map(String::as_str),
+                ))
+            }
+            _ => return Err(CommandError::Unknown(args.command)),
+        };
+
+        args.done()?;
+        Ok(parsed)
+    }
+}
+
+/// Candidates for the word under the cursor of the command bar
+#[derive(Debug, PartialEq, Eq)]
+pub struct Completion {
+    /// Input before the word being completed
+    prefix: String,
+    pub candidates: Vec<String>,
+    pub selected: Option<usize>,
+}
+
+impl Completion {
+    /// Completes the last word of `input`, a command name for the first word
+    /// and then the arguments of that command. `task_count` is the number of
+    /// tasks in the view, for task indexes.
+    pub fn new(input: &str, projects: &[String], task_count: usize) -> Self {
+        let input = input.strip_prefix(':').unwrap_or(input);
+        let start = input
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map(|(i, c)| i + c.len_utf8())
+            .unwrap_or(0);
+        let (prefix, word) = input.split_at(start);
+        let args = prefix.split_whitespace().collect::<Vec<&str>>();
+
+        let candidates: Vec<String> = match args.as_slice() {
+            [] => COMMANDS
+                .iter()
+                .flat_map(|c| {
+                    std::iter::once(c.name).chain(c.aliases.iter().copied())
+                })
+                .map(String::from)
+                .collect(),
+            [name] => match find_command(name).map(|c| c.name) {
+                Some("o") | Some("pdel") => projects.to_vec(),
+                Some("tmod") | Some("tdel") | Some("tp") | Some("due") => {
+                    (0..task_count).map(|i| i.to_string()).collect()
+                }
+                Some("sf") | Some("st") => {
+                    vec!["true".to_string(), "false".to_string()]
+                }
+                _ => Vec::new(),
+            },
+            _ => Vec::new(),
+        };
+
+        Completion {
+            prefix: format!(":{}", prefix),
+            candidates: candidates
+                .into_iter()
+                .filter(|c| c.starts_with(word))
+                .collect(),
+            selected: None,
+        }
+    }
+
+    /// Moves to the next or previous candidate, wrapping around, and returns
+    /// the input with it filled in
+    pub fn cycle(&mut self, forward: bool) -> Option<String> {
+        let len = self.candidates.len();
+        if len == 0 {
+            return None;
+        }
+        let selected = match (self.selected, forward) {
+            (None, true) => 0,
+            (None, false) => len - 1,
+            (Some(i), true) => (i + 1) % len,
+            (Some(i), false) => (i + len - 1) % len,
+        };
+        self.selected = Some(selected);
+        Some(format!("{}{}", self.prefix, self.candidates[selected]))
+    }
+}
+
+fn find_command(name: &str) -> Option<&'static CommandHelp> {
+    COMMANDS
+        .iter()
+        .find(|c| c.name == name || c.aliases.contains(&name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn projects() -> Vec<String> {
+        vec!["inbox".to_string(), "infra".to_string(), "work".to_string()]
+    }
+
+    #[test]
+    fn completes_command_names_and_aliases() {
+        let mut completion = Completion::new(":d", &projects(), 0);
+        assert_eq!(completion.candidates, vec!["delete", "due"]);
+        assert_eq!(completion.cycle(true), Some(":delete".to_string()));
+        assert_eq!(completion.cycle(true), Some(":due".to_string()));
+        assert_eq!(completion.cycle(true), Some(":delete".to_string()));
+        assert_eq!(completion.cycle(false), Some(":due".to_string()));
+    }
+
+    #[test]
+    fn completes_arguments_of_the_command() {
+        let mut completion = Completion::new(":open in", &projects(), 0);
+        assert_eq!(completion.candidates, vec!["inbox", "infra"]);
+        assert_eq!(completion.cycle(false), Some(":open infra".to_string()));
+
+        let completion = Completion::new(":tp ", &projects(), 3);
+        assert_eq!(completion.candidates, vec!["0", "1", "2"]);
+
+        let completion = Completion::new(":sf t", &projects(), 0);
+        assert_eq!(completion.candidates, vec!["true"]);
+
+        let mut completion = Completion::new(":q ", &projects(), 0);
+        assert!(completion.candidates.is_empty());
+        assert_eq!(completion.cycle(true), None);
+    }
+
+    #[test]
+    fn completes_after_any_whitespace() {
+        let mut completion = Completion::new(":o\u{a0}w", &projects(), 0);
+        assert_eq!(completion.candidates, vec!["work"]);
+        assert_eq!(completion.cycle(true), Some(":o\u{a0}work".to_string()));
+    }
+
+    fn parse(input: &str) -> Result<VimCommand, CommandError> {
+        input.parse::<VimCommand>()
+    }
+
+    #[test]
+    fn parses_every_command_and_alias() {
+        let filter = TagFilter {
+            include: vec!["home".to_string()],
+            exclude: vec!["work".to_string()],
+        };
+        let cases = [
+            (":q", VimCommand::Quit),
+            (":quit", VimCommand::Quit),
+            (":w", VimCommand::ProjectSave),
+            (":write", VimCommand::ProjectSave),
+            (":wq", VimCommand::ProjectSaveAndQuit),
+            (":resolve", VimCommand::Resolve),
+            (
+                ":tmod 1 buy milk",
+                VimCommand::TaskRename(1, "buy milk".into()),
+            ),
+            (":rename 1 milk", VimCommand::TaskRename(1, "milk".into())),
+            (":tdel 2", VimCommand::TaskDelete(2)),
+            (":delete 2", VimCommand::TaskDelete(2)),
+            (":tp 0 3", VimCommand::TaskSetPriority(0, 3)),
+            (":priority 0 1", VimCommand::TaskSetPriority(0, 1)),
+            (":due 4 +2d", VimCommand::TaskSetDue(4, "+2d".into())),
+            (":o work", VimCommand::ProjectOpen("work".into())),
+            (":open work", VimCommand::ProjectOpen("work".into())),
+            (":n home", VimCommand::ProjectNew("home".into())),
+            (":new home", VimCommand::ProjectNew("home".into())),
+            (":pdel home", VimCommand::ProjectDelete("home".into())),
+            (":sf true", VimCommand::ShowFinished(true)),
+            (":st false", VimCommand::ShowToday(false)),
+            (":filter +home -+work", VimCommand::Filter(filter)),
+            (":help", VimCommand::Help),
+            (":", VimCommand::None),
+        ];
+        for (input, expected) in cases.iter() {
+            assert_eq!(parse(input).as_ref(), Ok(expected), "{}", input);
+        }
+
+        // A new command or alias needs a case above
+        let tested = cases
+            .iter()
+            .filter_map(|(input, _)| input[1..].split_whitespace().next())
+            .collect::<Vec<&str>>();
+        for command in COMMANDS.iter() {
+            for name in std::iter::once(&command.name).chain(command.aliases) {
+                assert!(tested.contains(name), "untested command {}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn missing_arguments_name_what_is_expected() {
+        assert_eq!(
+            parse(":tdel"),
+            Err(CommandError::Missing {
+                command: "tdel".to_string(),
+                expected: "task index",
+            })
+        );
+        assert_eq!(
+            parse(":rename 1"),
+            Err(CommandError::Missing {
+                command: "rename".to_string(),
+                expected: "task text",
+            })
+        );
+        assert_eq!(
+            parse(":open"),
+            Err(CommandError::Missing {
+                command: "open".to_string(),
+                expected: "project name",
+            })
+        );
+    }
+
+    #[test]
+    fn bad_values_are_invalid() {
+        assert_eq!(
+            parse(":tmod abc foo"),
+            Err(CommandError::Invalid {
+                command: "tmod".to_string(),
+                expected: "task index",
+                value: "abc".to_string(),
+            })
+        );
+        assert_eq!(
+            parse(":tp 1 4"),
+            Err(CommandError::Invalid {
+                command: "tp".to_string(),
+                expected: "priority 0 to 3",
+                value: "4".to_string(),
+            })
+        );
+        assert_eq!(
+            parse(":sf maybe"),
+            Err(CommandError::Invalid {
+                command: "sf".to_string(),
+                expected: "true or false",
+                value: "maybe".to_string(),
+            })
+        );
+        assert_eq!(
+            parse(":frobnicate"),
+            Err(CommandError::Unknown("frobnicate".to_string()))
+        );
+    }
+
+    #[test]
+    fn quotes_and_backslashes_keep_spaces() {
+        assert_eq!(
+            tokenize(r#"tmod 2 "buy  milk" 'and eggs'"#),
+            Ok(vec![
+                "tmod".to_string(),
+                "2".to_string(),
+                "buy  milk".to_string(),
+                "and eggs".to_string(),
+            ])
+        );
+        assert_eq!(
+            tokenize(r#"o "" my\ project"#),
+            Ok(vec![
+                "o".to_string(),
+                String::new(),
+                "my project".to_string()
+            ])
+        );
+        assert_eq!(
+            parse(r#":tmod 0 "say \"hi\"""#),
+            Ok(VimCommand::TaskRename(0, r#"say "hi""#.to_string()))
+        );
+        assert_eq!(
+            parse(r":o my\ project"),
+            Ok(VimCommand::ProjectOpen("my project".to_string()))
+        );
+    }
+
+    #[test]
+    fn unterminated_quotes_are_rejected() {
+        assert_eq!(
+            parse(r#":tmod 1 "buy milk"#),
+            Err(CommandError::UnterminatedQuote)
+        );
+        assert_eq!(parse(":o 'work"), Err(CommandError::UnterminatedQuote));
+    }
+
+    #[test]
+    fn extra_arguments_are_rejected() {
+        assert_eq!(
+            parse(":q now"),
+            Err(CommandError::TooManyArguments("q".to_string()))
+        );
+        assert_eq!(
+            parse(":tdel 1 2"),
+            Err(CommandError::TooManyArguments("tdel".to_string()))
+        );
+        assert_eq!(
+            parse(":open work home"),
+            Err(CommandError::TooManyArguments("open".to_string()))
+        );
+    }
+}
@@ -1,7 +1,9 @@
+use super::gist::DEFAULT_API_BASE_URL;
 use anyhow::anyhow;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
     fs,
     io::{stdin, Write},
     path::{Path, PathBuf},
@@ -10,6 +12,9 @@ use std::{
 const FILE_NAME: &str = "client.yml";
 const CONFIG_DIR: &str = ".config";
 const APP_CONFIG_DIR: &str = "barnacle";
+const PROJECTS_DIR: &str = "projects";
+const CACHE_DIR: &str = "cache";
+const COMMAND_HISTORY_FILE: &str = "command_history";
 
 pub const BANNER: &str = r#"
 888                                            888         
@@ -22,6 +27,14 @@ pub const BANNER: &str = r#"
 88888P" "Y888888888    888  888"Y888888 "Y8888P888 "Y8888 
 "#;
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    #[default]
+    Gist,
+    Local,
+}
+
 #[derive(Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ClientConfig {
     pub client_id: String,
@@ -29,10 +42,24 @@ pub struct ClientConfig {
     pub current_project: Option<String>,
     pub show_finished: Option<bool>,
     pub show_today: Option<bool>,
+    pub storage: Option<StorageBackend>,
+    /// Root of the Github API, e.g. `https://github.example.com/api/v3` for
+    /// Github Enterprise Server
+    pub api_base_url: Option<String>,
+    /// Extra Global mode bindings, e.g. `"ctrl-f": finish_task` or
+    /// `"g t": top`
+    pub keybindings: Option<BTreeMap<String, String>>,
+    /// Set to false to leave the mouse to the terminal, e.g. for selecting
+    /// text
+    pub mouse: Option<bool>,
 }
 
 pub struct ConfigPaths {
     pub config_file_path: PathBuf,
+    pub projects_dir_path: PathBuf,
+    pub cache_dir_path: PathBuf,
+    /// Previous `:` commands, one per line
+    pub command_history_path: PathBuf,
 }
 
 impl ClientConfig {
@@ -43,9 +70,23 @@ impl ClientConfig {
             current_project: Some("".to_string()),
             show_finished: Some(false),
             show_today: Some(false),
+            storage: Some(StorageBackend::Gist),
+            api_base_url: None,
+            keybindings: None,
+            mouse: None,
         }
     }
 
+    pub fn mouse_enabled(&self) -> bool {
+        self.mouse.unwrap_or(true)
+    }
+
+    pub fn api_base_url(&self) -> String {
+        self.api_base_url
+            .clone()
+            .unwrap_or_else(|| DEFAULT_API_BASE_URL.to_string())
+    }
+
     pub fn get_or_build_paths(&self) -> Result<ConfigPaths> {
         match dirs::home_dir() {
             Some(home) => {
@@ -65,6 +106,10 @@ impl ClientConfig {
 
                 let paths = ConfigPaths {
                     config_file_path: config_file_path.to_path_buf(),
+                    projects_dir_path: app_config_dir.join(PROJECTS_DIR),
+                    cache_dir_path: app_config_dir.join(CACHE_DIR),
+                    command_history_path: app_config_dir
+                        .join(COMMAND_HISTORY_FILE),
                 };
 
                 Ok(paths)
@@ -94,6 +139,10 @@ impl ClientConfig {
             self.current_project = config_yml.current_project;
             self.show_finished = config_yml.show_finished;
             self.show_today = config_yml.show_today;
+            self.storage = config_yml.storage;
+            self.api_base_url = config_yml.api_base_url;
+            self.keybindings = config_yml.keybindings;
+            self.mouse = config_yml.mouse;
 
             Ok(())
         } else {
@@ -113,19 +162,26 @@ impl ClientConfig {
                 "You are now ready to authenticate with Gist!",
             ];
 
-            let mut number = 1;
-            for item in instructions.iter() {
-                println!("  {}. {}", number, item);
-                number += 1;
+            for (number, item) in instructions.iter().enumerate() {
+                println!("  {}. {}", number + 1, item);
             }
+            println!(
+                "\nLeave the token empty to keep your projects in {} instead.",
+                paths.projects_dir_path.display()
+            );
 
             let mut client_secret = String::new();
             println!("\nEnter your Github Personal Token: ");
             stdin().read_line(&mut client_secret)?;
 
             let mut client_id = String::new();
-            println!("\nEnter your Gist id: ");
-            stdin().read_line(&mut client_id)?;
+            let storage = if client_secret.trim().is_empty() {
+                StorageBackend::Local
+            } else {
+                println!("\nEnter your Gist id: ");
+                stdin().read_line(&mut client_id)?;
+                StorageBackend::Gist
+            };
 
             let config_yml = ClientConfig {
                 client_id: client_id.trim().to_string(),
@@ -133,6 +189,10 @@ impl ClientConfig {
                 current_project: Some("inbox".to_string()),
                 show_finished: Some(false),
                 show_today: Some(false),
+                storage: Some(storage),
+                api_base_url: None,
+                keybindings: None,
+                mouse: None,
             };
 
             let content_yml = serde_yaml::to_string(&config_yml)?;
@@ -143,6 +203,7 @@ impl ClientConfig {
             self.client_id = config_yml.client_id;
             self.client_secret = config_yml.client_secret;
             self.current_project = config_yml.current_project;
+            self.storage = config_yml.storage;
 
             Ok(())
         }
@@ -1,27 +1,62 @@
 use std::convert::{TryFrom, TryInto};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
 use crossterm::event;
+use tokio::sync::mpsc::UnboundedSender;
 
-use super::EventIterator;
+use super::Event;
 
-pub struct CrosstermEvents {}
+/// How long the input thread waits for the terminal before checking
+/// whether it should stop
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
 
-impl CrosstermEvents {
-    /// Creates a new `CrosstermEvents`
-    pub fn new() -> Self {
-        Self {}
-    }
+/// Reads the terminal on its own thread, crossterm only has a blocking API
+pub struct InputThread {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
 }
 
-impl EventIterator for CrosstermEvents {
-    fn next_event(&mut self) -> std::io::Result<super::KeyEvent> {
-        loop {
-            if let event::Event::Key(k) = event::read()? {
-                if let Ok(k) = k.try_into() {
-                    return Ok(k);
+impl InputThread {
+    pub fn spawn(sender: UnboundedSender<Event>) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::clone(&stop);
+        let handle = thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                match event::poll(POLL_INTERVAL) {
+                    Ok(true) => {}
+                    Ok(false) => continue,
+                    Err(_) => break,
+                }
+
+                let event = match event::read() {
+                    Ok(event) => event,
+                    Err(_) => break,
+                };
+                let event = match event {
+                    event::Event::Key(k) => match k.try_into() {
+                        Ok(k) => Event::Key(k),
+                        Err(()) => continue,
+                    },
+                    event::Event::Mouse(m) => Event::Mouse(m),
+                    event::Event::Resize(w, h) => Event::Resize(w, h),
+                    _ => continue,
+                };
+                if sender.send(event).is_err() {
+                    break;
                 }
             }
-        }
+        });
+
+        InputThread { stop, handle }
+    }
+
+    /// Waits until the thread stopped reading, at most `POLL_INTERVAL`
+    pub fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.handle.join();
     }
 }
 
@@ -29,9 +64,8 @@ impl TryFrom<event::KeyEvent> for super::KeyEvent {
     type Error = ();
 
     fn try_from(event: event::KeyEvent) -> Result<Self, ()> {
-        match event.kind == event::KeyEventKind::Release {
-            true => return Err(()),
-            false => (),
+        if event.kind == event::KeyEventKind::Release {
+            return Err(());
         }
 
         let code = match event.code {
@@ -46,6 +80,8 @@ impl TryFrom<event::KeyEvent> for super::KeyEvent {
             event::KeyCode::PageUp => super::KeyCode::PageUp,
             event::KeyCode::PageDown => super::KeyCode::PageDown,
             event::KeyCode::Tab => super::KeyCode::Tab,
+            // Shift-Tab, the SHIFT modifier is added below
+            event::KeyCode::BackTab => super::KeyCode::Tab,
             event::KeyCode::Delete => super::KeyCode::Delete,
             event::KeyCode::Insert => super::KeyCode::Ins,
             event::KeyCode::F(f) => super::KeyCode::F(f),
@@ -57,7 +93,9 @@ impl TryFrom<event::KeyEvent> for super::KeyEvent {
 
         let mut modifiers = super::KeyModifiers::empty();
 
-        if event.modifiers.contains(event::KeyModifiers::SHIFT) {
+        if event.modifiers.contains(event::KeyModifiers::SHIFT)
+            || event.code == event::KeyCode::BackTab
+        {
             modifiers |= super::KeyModifiers::SHIFT;
         }
         if event.modifiers.contains(event::KeyModifiers::CONTROL) {
@@ -69,4 +107,4 @@ impl TryFrom<event::KeyEvent> for super::KeyEvent {
 
         Ok(super::KeyEvent { code, modifiers })
     }
-}
\ No newline at end of file
+}
@@ -29,10 +29,7 @@ impl KeyEvent {
 
 impl From<KeyCode> for KeyEvent {
     fn from(code: KeyCode) -> Self {
-        KeyEvent {
-            code,
-            modifiers: KeyModifiers::empty(),
-        }
+        KeyEvent::new(code, KeyModifiers::empty())
     }
 }
 
@@ -1,17 +1,101 @@
-use std::io;
+use async_trait::async_trait;
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 
 mod events;
 mod keys;
+#[cfg(test)]
+mod scripted;
 
-pub use self::events::CrosstermEvents;
+pub use self::events::InputThread;
+#[cfg(test)]
+pub use self::scripted::ScriptedEvents;
+pub use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
 pub use keys::{KeyCode, KeyEvent, KeyModifiers};
 
-pub fn get_events() -> impl EventIterator {
+/// How often `Event::Tick` is sent when nothing else happens
+pub const TICK_RATE: Duration = Duration::from_secs(1);
 
-    return CrosstermEvents::new();
+/// Anything the main loop redraws for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Key(KeyEvent),
+    Mouse(MouseEvent),
+    /// New size of the terminal in columns and rows
+    Resize(u16, u16),
+    Tick,
+    /// A background sync finished
+    SyncCompleted,
+    SyncFailed(String),
 }
 
-pub trait EventIterator {
-    /// Get the next event
-    fn next_event(&mut self) -> io::Result<KeyEvent>;
+/// Where the main loop gets its events from
+#[async_trait]
+pub trait EventIterator: Send {
+    /// Waits for the next event, `None` once there will be no more
+    async fn next_event(&mut self) -> Option<Event>;
+
+    /// Stops reading the terminal, e.g. while an editor runs in it
+    fn suspend(&mut self) {}
+
+    fn resume(&mut self) {}
+}
+
+/// Events from the terminal, a timer and background tasks merged into a
+/// single channel
+pub struct Events {
+    sender: UnboundedSender<Event>,
+    receiver: UnboundedReceiver<Event>,
+    input: Option<InputThread>,
+}
+
+impl Events {
+    /// Starts reading the terminal and sending a tick every `tick_rate`
+    pub fn new(tick_rate: Duration) -> Self {
+        let (sender, receiver) = unbounded_channel();
+
+        let ticks = sender.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(tick_rate);
+            loop {
+                interval.tick().await;
+                if ticks.send(Event::Tick).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let input = Some(InputThread::spawn(sender.clone()));
+        Events {
+            sender,
+            receiver,
+            input,
+        }
+    }
+
+    /// Lets other producers, like background syncs, send events
+    pub fn sender(&self) -> UnboundedSender<Event> {
+        self.sender.clone()
+    }
+
+}
+
+#[async_trait]
+impl EventIterator for Events {
+    /// Waits for the next event from any producer
+    async fn next_event(&mut self) -> Option<Event> {
+        self.receiver.recv().await
+    }
+
+    fn suspend(&mut self) {
+        if let Some(input) = self.input.take() {
+            input.stop();
+        }
+    }
+
+    fn resume(&mut self) {
+        if self.input.is_none() {
+            self.input = Some(InputThread::spawn(self.sender.clone()));
+        }
+    }
 }
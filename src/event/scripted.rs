@@ -0,0 +1,41 @@
+use std::collections::VecDeque;
+
+use async_trait::async_trait;
+
+use super::{Event, EventIterator, KeyCode, KeyEvent};
+
+/// Replays a fixed list of events, for driving the main loop in tests
+#[derive(Default)]
+pub struct ScriptedEvents {
+    events: VecDeque<Event>,
+}
+
+impl ScriptedEvents {
+    pub fn new<I: IntoIterator<Item = Event>>(events: I) -> Self {
+        ScriptedEvents {
+            events: events.into_iter().collect(),
+        }
+    }
+
+    pub fn key(mut self, key: KeyEvent) -> Self {
+        self.events.push_back(Event::Key(key));
+        self
+    }
+
+    pub fn code(self, code: KeyCode) -> Self {
+        self.key(code.into())
+    }
+
+    /// Presses every char of `text` in turn
+    pub fn text(self, text: &str) -> Self {
+        text.chars()
+            .fold(self, |events, c| events.code(KeyCode::Char(c)))
+    }
+}
+
+#[async_trait]
+impl EventIterator for ScriptedEvents {
+    async fn next_event(&mut self) -> Option<Event> {
+        self.events.pop_front()
+    }
+}
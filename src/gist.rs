@@ -1,16 +1,146 @@
 use anyhow::anyhow;
 use anyhow::Result;
+use chrono::{DateTime, Local, TimeZone};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use yukikaze::client::{Client, Request};
+use std::fmt;
+use yukikaze::client::{Client, Request, Response};
+use yukikaze::extractor::BodyReadError;
 use yukikaze::matsu;
 
-pub const URL: &str = "https://api.github.com/gists";
+pub const DEFAULT_API_BASE_URL: &str = "https://api.github.com";
+
+/// Largest page size the gist listing allows
+const PER_PAGE: usize = 100;
+
+/// Everything that can go wrong talking to the Gist API
+#[derive(Debug)]
+pub enum GistError {
+    /// The token is missing, invalid or lacks the gist scope
+    Auth,
+    NotFound,
+    /// Out of API requests until `reset`, when Github tells us
+    RateLimited {
+        reset: Option<DateTime<Local>>,
+    },
+    /// The request never got a response
+    Network(String),
+    MalformedJson(String),
+    Timeout,
+    InvalidUrl(String),
+    /// Any other unsuccessful status code
+    Status(u16),
+}
+
+pub type GistResult<T> = std::result::Result<T, GistError>;
+
+impl GistError {
+    /// Whether the request may well succeed once connectivity returns
+    pub fn is_offline(&self) -> bool {
+        matches!(self, GistError::Network(_) | GistError::Timeout)
+    }
+
+    fn from_response(response: &Response) -> GistError {
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<i64>().ok())
+        };
+
+        match response.status().as_u16() {
+            401 => GistError::Auth,
+            403 | 429
+                if header("x-ratelimit-remaining") == Some(0)
+                    || header("retry-after").is_some() =>
+            {
+                let reset = match header("x-ratelimit-reset") {
+                    Some(epoch) => Local.timestamp_opt(epoch, 0).single(),
+                    None => header("retry-after").map(|secs| {
+                        Local::now() + chrono::Duration::seconds(secs)
+                    }),
+                };
+                GistError::RateLimited { reset }
+            }
+            403 => GistError::Auth,
+            404 => GistError::NotFound,
+            status => GistError::Status(status),
+        }
+    }
+}
+
+impl fmt::Display for GistError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GistError::Auth => {
+                write!(f, "Github rejected the token, check client_secret")
+            }
+            GistError::NotFound => write!(f, "Gist not found"),
+            GistError::RateLimited { reset: Some(reset) } => write!(
+                f,
+                "Github rate limit reached, resets at {}",
+                reset.format("%H:%M")
+            ),
+            GistError::RateLimited { reset: None } => {
+                write!(f, "Github rate limit reached")
+            }
+            GistError::Network(e) => write!(f, "Network error: {}", e),
+            GistError::MalformedJson(e) => write!(f, "Malformed JSON: {}", e),
+            GistError::Timeout => write!(f, "Request to Github timed out"),
+            GistError::InvalidUrl(e) => write!(f, "Invalid URL: {}", e),
+            GistError::Status(status) => {
+                write!(f, "Github responded with status {}", status)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GistError {}
+
+impl From<BodyReadError> for GistError {
+    fn from(error: BodyReadError) -> Self {
+        match error {
+            BodyReadError::JsonError(e) => {
+                GistError::MalformedJson(e.to_string())
+            }
+            e => GistError::Network(e.to_string()),
+        }
+    }
+}
+
+impl From<http::uri::InvalidUri> for GistError {
+    fn from(error: http::uri::InvalidUri) -> Self {
+        GistError::InvalidUrl(error.to_string())
+    }
+}
+
+impl From<serde_json::Error> for GistError {
+    fn from(error: serde_json::Error) -> Self {
+        GistError::MalformedJson(error.to_string())
+    }
+}
+
+/// Sends `request`, turning anything but a successful response into an error
+async fn send(request: Request) -> GistResult<Response> {
+    let client = Client::default();
+    let response = match matsu!(client.send(request)) {
+        Ok(Ok(response)) => response,
+        Ok(Err(e)) => return Err(GistError::Network(e.to_string())),
+        Err(_) => return Err(GistError::Timeout),
+    };
+
+    if !response.is_success() {
+        return Err(GistError::from_response(&response));
+    }
+    Ok(response)
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GistUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
-    pub files: HashMap<String, FileUpdate>,
+    pub files: HashMap<String, Option<FileUpdate>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -26,46 +156,42 @@ impl GistUpdate {
         old_name: String,
         new_name: Option<String>,
     ) -> Self {
-        let mut hm: HashMap<String, FileUpdate> = HashMap::new();
+        let mut hm: HashMap<String, Option<FileUpdate>> = HashMap::new();
         hm.insert(
             old_name,
-            FileUpdate {
+            Some(FileUpdate {
                 content: cont,
                 filename: new_name,
-            },
+            }),
         );
         GistUpdate {
             description: Some(desc),
             files: hm,
         }
     }
-    pub async fn update(&self, url: &str, token: &str) -> Result<()> {
-        let client = Client::default();
-        let mut resp: Request =
+
+    /// An update that removes the file `name` from the gist
+    pub fn delete(name: String) -> Self {
+        let mut hm: HashMap<String, Option<FileUpdate>> = HashMap::new();
+        hm.insert(name, None);
+        GistUpdate {
+            description: None,
+            files: hm,
+        }
+    }
+
+    pub async fn update(&self, url: &str, token: &str) -> GistResult<()> {
+        let mut request: Request =
             Request::put(url)?.bearer_auth(token).json(self)?;
 
-        *resp.method_mut() = http::Method::PATCH;
+        *request.method_mut() = http::Method::PATCH;
 
-        let mut response = matsu!(client.send(resp))
-            .expect("Not timedout")
-            .expect("Successful");
-        matsu!(response.text()).expect("To read HTML");
+        let mut response = send(request).await?;
+        matsu!(response.text())?;
         Ok(())
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct GistPost {
-    pub description: String,
-    pub public: bool,
-    pub files: HashMap<String, FilePost>,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct FilePost {
-    pub content: String,
-}
-
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ResponseGist {
     #[serde(rename = "id")]
@@ -97,18 +223,47 @@ pub struct ListGist {
     pub list: Vec<ResponseGist>,
 }
 
-pub async fn get_gist_file(url: &str, token: &str) -> Result<String> {
-    let client = Client::default();
-    let resp: Request = Request::get(url)?.bearer_auth(token).empty();
+/// The gists endpoint of the API at `base_url`
+fn gists_url(base_url: &str) -> String {
+    format!("{}/gists", base_url.trim_end_matches('/'))
+}
 
-    let mut response = matsu!(client.send(resp))
-        .expect("Not timedout")
-        .expect("Successful");
-    if response.is_success() {
-        let buf = matsu!(response.text()).expect("To read HTML");
-        return Ok(buf);
-    }
-    Err(anyhow!("Failed to get list"))
+/// Fetches the single gist `id`
+pub async fn get_gist(
+    base_url: &str,
+    id: &str,
+    token: &str,
+) -> GistResult<ResponseGist> {
+    let url = format!("{}/{}", gists_url(base_url), id);
+    let request: Request = Request::get(url)?.bearer_auth(token).empty();
+
+    let mut response = send(request).await?;
+    Ok(matsu!(response.json())?)
+}
+
+/// The `rel="next"` target of the `Link` header Github paginates with
+fn next_page_url(response: &Response) -> Option<String> {
+    let link = response.headers().get("link")?.to_str().ok()?;
+    link.split(',').find_map(|part| {
+        let mut params = part.split(';');
+        let url = params.next()?.trim();
+        if params.any(|p| p.trim() == "rel=\"next\"") {
+            Some(
+                url.trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string(),
+            )
+        } else {
+            None
+        }
+    })
+}
+
+pub async fn get_gist_file(url: &str, token: &str) -> GistResult<String> {
+    let request: Request = Request::get(url)?.bearer_auth(token).empty();
+
+    let mut response = send(request).await?;
+    Ok(matsu!(response.text())?)
 }
 
 impl ListGist {
@@ -116,18 +271,42 @@ impl ListGist {
         ListGist { list }
     }
 
-    pub async fn get_update_list_gist(token: &str) -> Result<ListGist> {
-        let client = Client::default();
-        let resp: Request = Request::get(URL)?.bearer_auth(token).empty();
-        let mut response = matsu!(client.send(resp))
-            .expect("Not timedout")
-            .expect("Successful");
-        if response.is_success() {
-            let list_gist: Vec<ResponseGist> =
-                matsu!(response.json()).expect("To read HTML");
-            return Ok(ListGist::new(list_gist));
+    /// Lists every gist of the user, following all pages
+    pub async fn get_update_list_gist(
+        base_url: &str,
+        token: &str,
+    ) -> GistResult<ListGist> {
+        let mut list_gist: Vec<ResponseGist> = Vec::new();
+        let mut next_url =
+            Some(format!("{}?per_page={}", gists_url(base_url), PER_PAGE));
+
+        while let Some(url) = next_url {
+            let request: Request =
+                Request::get(url)?.bearer_auth(token).empty();
+
+            let mut response = send(request).await?;
+            next_url = next_page_url(&response);
+            let mut page: Vec<ResponseGist> = matsu!(response.json())?;
+            list_gist.append(&mut page);
+        }
+
+        Ok(ListGist::new(list_gist))
+    }
+
+    /// Fetches the gist `id` directly, falling back to listing every gist
+    /// when `id` is only a prefix of the full id
+    pub async fn get_list_for_gist(
+        base_url: &str,
+        id: &str,
+        token: &str,
+    ) -> GistResult<ListGist> {
+        match get_gist(base_url, id, token).await {
+            Ok(gist) => Ok(ListGist::new(vec![gist])),
+            Err(GistError::NotFound) => {
+                ListGist::get_update_list_gist(base_url, token).await
+            }
+            Err(e) => Err(e),
         }
-        Err(anyhow!("unsuccessful get list gist"))
     }
 
     pub fn search_url_gist<T: AsRef<str>>(&self, id: T) -> Result<String> {
@@ -159,8 +338,11 @@ impl ListGist {
         id: T,
         file: T,
     ) -> Result<String> {
+        if id.as_ref().len() < 5 {
+            return Err(anyhow!("id invalid"));
+        }
         for gist in self.list.clone() {
-            if gist.id == id.as_ref() {
+            if gist.id.starts_with(id.as_ref()) {
                 for (_, v) in gist.files {
                     if v.name == file.as_ref() {
                         return Ok(v.raw_url);
@@ -171,3 +353,312 @@ impl ListGist {
         Err(anyhow!("id not exist"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::WorkItem;
+    use crate::storage::{GistStorage, ProjectCache, Storage, SyncQueue};
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::Arc;
+    use std::{fs, thread};
+    use uuid::Uuid;
+    use yukikaze::hyper;
+
+    fn with_link(link: Option<&str>) -> Response {
+        let mut builder = hyper::Response::builder();
+        if let Some(link) = link {
+            builder = builder.header("link", link);
+        }
+        Response::new(builder.body(hyper::Body::empty()).unwrap())
+    }
+
+    /// A raw HTTP response that closes the connection
+    fn reply(status: &str, headers: &[(&str, &str)], body: &str) -> String {
+        let mut response = format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            status,
+            body.len()
+        );
+        for (name, value) in headers {
+            response.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        response.push_str("\r\n");
+        response.push_str(body);
+        response
+    }
+
+    /// Method, path and body of the next request on `stream`
+    fn read_request(stream: &mut TcpStream) -> (String, String, String) {
+        let mut data = Vec::new();
+        let mut buf = [0; 4096];
+        let header_end = loop {
+            let n = stream.read(&mut buf).unwrap();
+            data.extend_from_slice(&buf[..n]);
+            if let Some(i) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+                break i + 4;
+            }
+        };
+
+        let head = String::from_utf8_lossy(&data[..header_end]).to_string();
+        let length = head
+            .lines()
+            .find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                if name.eq_ignore_ascii_case("content-length") {
+                    value.trim().parse::<usize>().ok()
+                } else {
+                    None
+                }
+            })
+            .unwrap_or(0);
+        while data.len() < header_end + length {
+            let n = stream.read(&mut buf).unwrap();
+            data.extend_from_slice(&buf[..n]);
+        }
+
+        let mut request_line = head.split_whitespace();
+        let method = request_line.next().unwrap_or("").to_string();
+        let path = request_line.next().unwrap_or("").to_string();
+        let body = String::from_utf8_lossy(&data[header_end..]).to_string();
+        (method, path, body)
+    }
+
+    /// Answers requests for the paths in `routes` and 404s the rest
+    fn serve(listener: TcpListener, routes: Vec<(String, String)>) {
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let (_, path, _) = read_request(&mut stream);
+                let response = routes
+                    .iter()
+                    .find(|(p, _)| *p == path)
+                    .map(|(_, r)| r.clone())
+                    .unwrap_or_else(|| reply("404 Not Found", &[], "{}"));
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+    }
+
+    /// Serves the gist `id` holding an `inbox` file. Like on Github, every
+    /// PATCH adds a revision and the raw url points at a single revision.
+    fn serve_gist(listener: TcpListener, base_url: String, id: &'static str) {
+        thread::spawn(move || {
+            let mut revisions = vec!["[]".to_string()];
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let (method, path, body) = read_request(&mut stream);
+                let response = if method == "PATCH" {
+                    let update: GistUpdate =
+                        serde_json::from_str(&body).unwrap();
+                    if let Some(Some(file)) = update.files.get("inbox") {
+                        revisions.push(file.content.clone());
+                    }
+                    reply("200 OK", &[], "{}")
+                } else if let Some(revision) = path.strip_prefix("/raw/") {
+                    let revision = revision.parse::<usize>().unwrap();
+                    reply("200 OK", &[], &revisions[revision])
+                } else if path == format!("/gists/{}", id) {
+                    let gist = format!(
+                        r#"{{"id":"{0}","url":"{1}/gists/{0}","description":null,"files":{{"inbox":{{"filename":"inbox","type":"application/json","language":"JSON","raw_url":"{1}/raw/{2}","size":2}}}}}}"#,
+                        id,
+                        base_url,
+                        revisions.len() - 1
+                    );
+                    reply("200 OK", &[], &gist)
+                } else {
+                    reply("404 Not Found", &[], "{}")
+                };
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+    }
+
+    fn listen() -> (TcpListener, String) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        (listener, base_url)
+    }
+
+    fn gist_json(id: &str) -> String {
+        format!(
+            r#"{{"id":"{0}","url":"https://example.com/{0}","description":null,"files":{{"inbox":{{"filename":"inbox","type":"application/json","language":"JSON","raw_url":"https://example.com/{0}/inbox","size":2}}}}}}"#,
+            id
+        )
+    }
+
+    #[test]
+    fn next_page_url_follows_rel_next() {
+        let response = with_link(Some(
+            "<https://api.github.com/gists?page=2>; rel=\"next\", \
+             <https://api.github.com/gists?page=5>; rel=\"last\"",
+        ));
+        assert_eq!(
+            next_page_url(&response).as_deref(),
+            Some("https://api.github.com/gists?page=2")
+        );
+
+        let last_page = with_link(Some(
+            "<https://api.github.com/gists?page=1>; rel=\"first\", \
+             <https://api.github.com/gists?page=4>; rel=\"prev\"",
+        ));
+        assert_eq!(next_page_url(&last_page), None);
+        assert_eq!(next_page_url(&with_link(None)), None);
+    }
+
+    #[tokio::test]
+    async fn error_statuses_are_mapped() {
+        let (listener, base_url) = listen();
+        let route =
+            |id: &str, response: String| (format!("/gists/{}", id), response);
+        serve(
+            listener,
+            vec![
+                route("auth", reply("401 Unauthorized", &[], "{}")),
+                route("scope", reply("403 Forbidden", &[], "{}")),
+                route(
+                    "limited",
+                    reply(
+                        "403 Forbidden",
+                        &[
+                            ("x-ratelimit-remaining", "0"),
+                            ("x-ratelimit-reset", "1790000000"),
+                        ],
+                        "{}",
+                    ),
+                ),
+                route(
+                    "retry",
+                    reply(
+                        "429 Too Many Requests",
+                        &[("retry-after", "60")],
+                        "",
+                    ),
+                ),
+                route("broken", reply("500 Internal Server Error", &[], "")),
+            ],
+        );
+
+        let error = |id: &'static str| {
+            let base_url = base_url.clone();
+            async move { get_gist(&base_url, id, "token").await.unwrap_err() }
+        };
+        assert!(matches!(error("auth").await, GistError::Auth));
+        assert!(matches!(error("scope").await, GistError::Auth));
+        assert!(matches!(error("missing").await, GistError::NotFound));
+        assert!(matches!(error("broken").await, GistError::Status(500)));
+
+        match error("limited").await {
+            GistError::RateLimited { reset } => assert_eq!(
+                reset,
+                Local.timestamp_opt(1_790_000_000, 0).single()
+            ),
+            e => panic!("expected a rate limit, got {:?}", e),
+        }
+        assert!(matches!(
+            error("retry").await,
+            GistError::RateLimited { reset: Some(_) }
+        ));
+    }
+
+    #[tokio::test]
+    async fn gist_prefixes_fall_back_to_listing_every_page() {
+        let (listener, base_url) = listen();
+        let first_page = "/gists?per_page=100";
+        let second_page = "/gists?per_page=100&page=2";
+        let link = format!("<{}{}>; rel=\"next\"", base_url, second_page);
+        serve(
+            listener,
+            vec![
+                (
+                    "/gists/abcdef123".to_string(),
+                    reply("200 OK", &[], &gist_json("abcdef123")),
+                ),
+                (
+                    "/gists/denied".to_string(),
+                    reply("401 Unauthorized", &[], "{}"),
+                ),
+                (
+                    first_page.to_string(),
+                    reply(
+                        "200 OK",
+                        &[("link", &link)],
+                        &format!("[{}]", gist_json("0123456")),
+                    ),
+                ),
+                (
+                    second_page.to_string(),
+                    reply(
+                        "200 OK",
+                        &[],
+                        &format!("[{}]", gist_json("abcdef123")),
+                    ),
+                ),
+            ],
+        );
+
+        let list = ListGist::get_list_for_gist(&base_url, "abcdef123", "token")
+            .await
+            .unwrap();
+        assert_eq!(list.list.len(), 1);
+
+        let list = ListGist::get_list_for_gist(&base_url, "abcde", "token")
+            .await
+            .unwrap();
+        assert_eq!(list.list.len(), 2);
+        assert_eq!(
+            list.get_url_gist_file("abcde", "inbox").unwrap(),
+            "https://example.com/abcdef123/inbox"
+        );
+        assert!(list.get_url_gist_file("", "inbox").is_err());
+
+        // Only a missing gist is worth listing every page for
+        let denied =
+            ListGist::get_list_for_gist(&base_url, "denied", "token").await;
+        assert!(matches!(denied, Err(GistError::Auth)));
+    }
+
+    fn task(content: &str) -> WorkItem {
+        let mut item = WorkItem::from_input(content);
+        // The cache only keeps whole seconds
+        item.created_time =
+            Local.with_ymd_and_hms(2026, 10, 1, 9, 0, 0).unwrap();
+        item
+    }
+
+    fn contents(tasks: &[WorkItem]) -> Vec<&str> {
+        tasks.iter().filter_map(|t| t.content.as_deref()).collect()
+    }
+
+    #[tokio::test]
+    async fn second_push_merges_against_the_new_revision() {
+        let (listener, base_url) = listen();
+        serve_gist(listener, base_url.clone(), "abcdef123");
+        let remote = Arc::new(GistStorage::new(
+            base_url,
+            "abcdef123".to_string(),
+            "token".to_string(),
+        ));
+        let cache_dir =
+            std::env::temp_dir().join(format!("barnacle-{}", Uuid::new_v4()));
+        let queue = SyncQueue::new(
+            remote.clone(),
+            ProjectCache::new(cache_dir.clone()),
+        );
+
+        let mut tasks = vec![task("buy milk")];
+        queue.save("inbox", &tasks).unwrap();
+        queue.push().await.unwrap();
+        let uploaded = remote.load_project("inbox").await.unwrap();
+        assert_eq!(contents(&uploaded), vec!["buy milk"]);
+
+        tasks.push(task("walk dog"));
+        queue.save("inbox", &tasks).unwrap();
+        queue.push().await.unwrap();
+        let uploaded = remote.load_project("inbox").await.unwrap();
+        assert_eq!(contents(&uploaded), vec!["buy milk", "walk dog"]);
+
+        let _ = fs::remove_dir_all(&cache_dir);
+    }
+}
@@ -0,0 +1,400 @@
+//! Runs the main loop headless against ratatui's `TestBackend`, with
+//! projects kept in memory and key presses replayed from a script
+
+use crate::app::{App, AppAction, AppMode, WorkItem};
+use crate::config::ClientConfig;
+use crate::event::{
+    Event, KeyCode, MouseButton, MouseEvent, MouseEventKind, ScriptedEvents,
+};
+use crate::run_app;
+use crate::storage::{MemoryStorage, ProjectCache, Storage, SyncQueue};
+use ratatui::backend::TestBackend;
+use ratatui::buffer::Cell;
+use ratatui::style::Color;
+use ratatui::Terminal;
+use std::{fs, path::PathBuf, sync::Arc};
+use uuid::Uuid;
+
+const WIDTH: u16 = 100;
+const HEIGHT: u16 = 20;
+
+/// Row of the first task below the table border and header
+const FIRST_ROW: u16 = 2;
+
+struct Harness {
+    terminal: Terminal<TestBackend>,
+    app: App,
+    remote: Arc<MemoryStorage>,
+    cache_dir: PathBuf,
+}
+
+impl Harness {
+    /// An app with an empty `inbox` project
+    fn new() -> Self {
+        let mut client_config = ClientConfig::new();
+        client_config.current_project = Some("inbox".to_string());
+
+        let cache_dir =
+            std::env::temp_dir().join(format!("barnacle-{}", Uuid::new_v4()));
+        let remote = Arc::new(MemoryStorage::default());
+        let sync_queue = SyncQueue::new(
+            remote.clone(),
+            ProjectCache::new(cache_dir.clone()),
+        );
+        let mut app = App::with_sync_queue(client_config, sync_queue);
+        app.load();
+
+        let terminal = Terminal::new(TestBackend::new(WIDTH, HEIGHT)).unwrap();
+        Harness {
+            terminal,
+            app,
+            remote,
+            cache_dir,
+        }
+    }
+
+    /// Replays `events` and draws the screen after the last one
+    async fn run(&mut self, events: ScriptedEvents) {
+        let mut events = events;
+        run_app(&mut self.terminal, &mut self.app, &mut events)
+            .await
+            .unwrap();
+    }
+
+    fn cell(&self, x: u16, y: u16) -> &Cell {
+        self.terminal.backend().buffer().get(x, y)
+    }
+
+    fn line(&self, y: u16) -> String {
+        (0..WIDTH)
+            .map(|x| self.cell(x, y).symbol.as_str())
+            .collect()
+    }
+
+    /// Text of the task table row showing the task at `index`
+    fn task_row(&self, index: u16) -> String {
+        self.line(FIRST_ROW + index)
+    }
+
+    /// Text inside the input box under the table
+    fn input_box(&self) -> String {
+        self.line(HEIGHT - 3)
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.cache_dir);
+    }
+}
+
+#[tokio::test]
+async fn started_task_is_shown_in_green() {
+    let mut harness = Harness::new();
+    let events = ScriptedEvents::default()
+        .text("ibuy milk")
+        .code(KeyCode::Enter)
+        .text("s")
+        // A second task takes the selection off the first one
+        .text("iwalk dog")
+        .code(KeyCode::Enter);
+    harness.run(events).await;
+
+    let row = harness.task_row(0);
+    assert!(row.contains("Started"), "{}", row);
+    assert!(row.contains("buy milk"), "{}", row);
+    assert_eq!(harness.cell(1, FIRST_ROW).fg, Color::LightGreen);
+
+    let row = harness.task_row(1);
+    assert!(row.contains("UnStarted"), "{}", row);
+    assert!(row.contains("walk dog"), "{}", row);
+    assert_eq!(harness.cell(1, FIRST_ROW + 1).fg, Color::LightBlue);
+}
+
+#[tokio::test]
+async fn delete_and_undo() {
+    let mut harness = Harness::new();
+    let events = ScriptedEvents::default()
+        .text("ibuy milk")
+        .code(KeyCode::Enter)
+        .text("dd");
+    harness.run(events).await;
+    assert!(!harness.task_row(0).contains("buy milk"));
+    assert!(harness.app.tasks.is_empty());
+
+    harness.run(ScriptedEvents::default().text("u")).await;
+    assert!(harness.task_row(0).contains("buy milk"));
+}
+
+#[tokio::test]
+async fn messages_clear_on_the_next_key() {
+    let mut harness = Harness::new();
+    harness.run(ScriptedEvents::default().text("u")).await;
+    let status = harness.line(HEIGHT - 1);
+    assert!(status.contains("Already at oldest change"), "{}", status);
+
+    harness.run(ScriptedEvents::default().text("j")).await;
+    let status = harness.line(HEIGHT - 1);
+    assert!(!status.contains("Already at oldest change"), "{}", status);
+}
+
+#[tokio::test]
+async fn undo_survives_a_sync() {
+    let mut harness = Harness::new();
+    let events = ScriptedEvents::default()
+        .text("ibuy milk")
+        .code(KeyCode::Enter)
+        .text("dd")
+        // Refreshing reloads the project from the cache
+        .text("r");
+    harness.run(events).await;
+
+    harness.run(ScriptedEvents::default().text("u")).await;
+    assert!(harness.task_row(0).contains("buy milk"));
+}
+
+#[tokio::test]
+async fn undo_does_not_revert_remote_changes() {
+    let mut harness = Harness::new();
+    let events = ScriptedEvents::default()
+        .text("ibuy milk")
+        .code(KeyCode::Enter)
+        .text("r");
+    harness.run(events).await;
+
+    let mut tasks = harness.app.tasks.clone();
+    tasks.push(WorkItem::from_input("walk dog"));
+    harness.remote.save_project("inbox", &tasks).await.unwrap();
+
+    harness
+        .run(ScriptedEvents::default().text("r").text("u"))
+        .await;
+    assert_eq!(harness.app.tasks.len(), 2);
+    assert!(harness.task_row(1).contains("walk dog"));
+    let status = harness.line(HEIGHT - 1);
+    assert!(status.contains("Already at oldest change"), "{}", status);
+}
+
+#[tokio::test]
+async fn unknown_command_shows_an_error() {
+    let mut harness = Harness::new();
+    let events = ScriptedEvents::default()
+        .text(":frobnicate")
+        .code(KeyCode::Enter);
+    harness.run(events).await;
+
+    let input = harness.input_box();
+    assert!(input.contains("Not a command: frobnicate"), "{}", input);
+}
+
+#[tokio::test]
+async fn deleting_a_project_asks_first() {
+    let mut harness = Harness::new();
+    let events = ScriptedEvents::default()
+        .text("ibuy milk")
+        .code(KeyCode::Enter)
+        .text(":pdel inbox")
+        .code(KeyCode::Enter);
+    harness.run(events).await;
+
+    let input = harness.input_box();
+    assert!(input.contains("Delete project inbox? (y/n)"), "{}", input);
+
+    harness.run(ScriptedEvents::default().text("n")).await;
+    let projects = harness.app.sync_queue.list_projects().unwrap();
+    assert_eq!(projects, vec!["inbox"]);
+    assert_eq!(harness.app.tasks.len(), 1);
+
+    let events = ScriptedEvents::default()
+        .text(":pdel inbox")
+        .code(KeyCode::Enter)
+        .text("y");
+    harness.run(events).await;
+    assert!(harness.app.sync_queue.list_projects().unwrap().is_empty());
+    assert!(harness.app.current_project.is_none());
+    assert!(harness.app.tasks.is_empty());
+}
+
+#[tokio::test]
+async fn double_click_shows_details() {
+    let mut harness = Harness::new();
+    let events = ScriptedEvents::default()
+        .text("ibuy milk")
+        .code(KeyCode::Enter);
+    harness.run(events).await;
+
+    let click = Event::Mouse(MouseEvent {
+        kind: MouseEventKind::Down(MouseButton::Left),
+        column: 20,
+        row: FIRST_ROW,
+        modifiers: crossterm::event::KeyModifiers::NONE,
+    });
+    harness
+        .run(ScriptedEvents::new(vec![click.clone(), click]))
+        .await;
+
+    assert!(harness.app.show_details);
+    assert!(harness.line(0).contains("Details:"), "{}", harness.line(0));
+}
+
+#[tokio::test]
+async fn quit_stops_the_loop() {
+    let mut harness = Harness::new();
+    let events = ScriptedEvents::default()
+        .text(":q")
+        .code(KeyCode::Enter)
+        .text("inever added")
+        .code(KeyCode::Enter);
+    harness.run(events).await;
+
+    assert!(harness.app.tasks.is_empty());
+}
+
+#[tokio::test]
+async fn handle_key_leaves_terminal_work_to_the_frontend() {
+    let mut harness = Harness::new();
+    harness
+        .run(
+            ScriptedEvents::default()
+                .text("ibuy milk")
+                .code(KeyCode::Enter),
+        )
+        .await;
+    let app = &mut harness.app;
+    let id = app.tasks[0].id.clone().unwrap();
+
+    assert_eq!(
+        app.handle_key(KeyCode::Char('e').into()).await,
+        AppAction::EditNotes {
+            id,
+            notes: String::new()
+        }
+    );
+
+    app.handle_key(KeyCode::Char('i').into()).await;
+    assert_eq!(
+        app.handle_key(KeyCode::Esc.into()).await,
+        AppAction::HideCursor
+    );
+    assert!(matches!(app.mode, AppMode::Global));
+
+    for c in ":q".chars() {
+        app.handle_key(KeyCode::Char(c).into()).await;
+    }
+    assert_eq!(app.handle_key(KeyCode::Enter.into()).await, AppAction::Quit);
+}
+
+/// Adds a task for every entry of `tasks`, in order
+fn insert_tasks(tasks: &[&str]) -> ScriptedEvents {
+    tasks
+        .iter()
+        .fold(ScriptedEvents::default(), |events, task| {
+            events.text("i").text(task).code(KeyCode::Enter)
+        })
+}
+
+fn selected_content(app: &App) -> Option<String> {
+    app.selected_task().and_then(|task| task.content.clone())
+}
+
+#[tokio::test]
+async fn filtering_keeps_the_selected_task() {
+    let mut harness = Harness::new();
+    harness.run(insert_tasks(&["a +x", "b", "c +x"])).await;
+    assert_eq!(selected_content(&harness.app).as_deref(), Some("c"));
+
+    let events = ScriptedEvents::default()
+        .text(":filter +x")
+        .code(KeyCode::Enter);
+    harness.run(events).await;
+
+    assert_eq!(harness.app.get_tree_view().len(), 2);
+    assert_eq!(harness.app.selected_index(), 1);
+    assert_eq!(selected_content(&harness.app).as_deref(), Some("c"));
+}
+
+#[tokio::test]
+async fn deleting_selects_the_next_task() {
+    let mut harness = Harness::new();
+    let events = insert_tasks(&["a", "b", "c"]).text("ggjdd");
+    harness.run(events).await;
+
+    assert_eq!(selected_content(&harness.app).as_deref(), Some("c"));
+    assert_eq!(harness.app.selected_index(), 1);
+}
+
+#[tokio::test]
+async fn selection_follows_a_task_sorted_to_the_top() {
+    let mut harness = Harness::new();
+    let events = insert_tasks(&["a", "b", "c"]).text("s");
+    harness.run(events).await;
+
+    assert_eq!(harness.app.selected_index(), 0);
+    assert_eq!(selected_content(&harness.app).as_deref(), Some("c"));
+    assert!(harness.task_row(0).contains("Started"));
+}
+
+#[tokio::test]
+async fn help_stops_scrolling_at_the_last_line() {
+    let mut harness = Harness::new();
+    let events = (0..10)
+        .fold(ScriptedEvents::default().text("?"), |events, _| {
+            events.code(KeyCode::PageDown)
+        });
+    harness.run(events.text("k")).await;
+
+    let max_scroll = harness.app.help_max_scroll.get();
+    assert!(max_scroll > 0);
+    // One line up from the bottom, not ten pages past it
+    assert_eq!(harness.app.help_scroll, max_scroll - 1);
+}
+
+/// Two of them match `buy`
+const SHOPPING: &[&str] = &["buy milk", "walk dog", "buy bread"];
+
+#[tokio::test]
+async fn search_selects_the_first_match() {
+    let mut harness = Harness::new();
+    harness
+        .run(insert_tasks(SHOPPING).text("/dog").code(KeyCode::Enter))
+        .await;
+
+    assert_eq!(selected_content(&harness.app).as_deref(), Some("walk dog"));
+    assert!(matches!(harness.app.mode, AppMode::Global));
+}
+
+#[tokio::test]
+async fn search_without_matches_says_so() {
+    let mut harness = Harness::new();
+    harness.run(insert_tasks(SHOPPING)).await;
+    let selected = selected_content(&harness.app);
+
+    let events = ScriptedEvents::default()
+        .text("/zebra")
+        .code(KeyCode::Enter);
+    harness.run(events).await;
+
+    assert_eq!(selected_content(&harness.app), selected);
+    let status = harness.line(HEIGHT - 1);
+    assert!(status.contains("Pattern not found: zebra"), "{}", status);
+}
+
+#[tokio::test]
+async fn search_matches_wrap_around() {
+    let mut harness = Harness::new();
+    let events = insert_tasks(SHOPPING)
+        .text("gg")
+        .text("/buy")
+        .code(KeyCode::Enter);
+    harness.run(events).await;
+    assert_eq!(selected_content(&harness.app).as_deref(), Some("buy milk"));
+
+    harness.run(ScriptedEvents::default().text("N")).await;
+    assert_eq!(selected_content(&harness.app).as_deref(), Some("buy bread"));
+
+    harness.run(ScriptedEvents::default().text("n")).await;
+    assert_eq!(selected_content(&harness.app).as_deref(), Some("buy milk"));
+
+    harness.run(ScriptedEvents::default().text("nn")).await;
+    assert_eq!(selected_content(&harness.app).as_deref(), Some("buy milk"));
+}
@@ -0,0 +1,645 @@
+use crate::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Everything a key can be bound to in Global mode
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    StartTask,
+    FinishTask,
+    WontFix,
+    Delete,
+    Paste,
+    Refresh,
+    FixIds,
+    Insert,
+    AddSubtask,
+    Command,
+    Search,
+    SearchNext,
+    SearchPrev,
+    Up,
+    Down,
+    Top,
+    Bottom,
+    HalfPageUp,
+    HalfPageDown,
+    PageUp,
+    PageDown,
+    PriorityUp,
+    PriorityDown,
+    Collapse,
+    Expand,
+    ToggleDetails,
+    EditNotes,
+    Undo,
+    Redo,
+    Clear,
+    Help,
+}
+
+impl Action {
+    const ALL: [(Action, &'static str, &'static str); 31] = [
+        (Action::StartTask, "start_task", "Start the selected task"),
+        (
+            Action::FinishTask,
+            "finish_task",
+            "Finish the selected task and its subtasks",
+        ),
+        (
+            Action::WontFix,
+            "wont_fix",
+            "Mark the selected task as won't fix",
+        ),
+        (
+            Action::Delete,
+            "delete",
+            "Delete the selected task and its subtasks",
+        ),
+        (Action::Paste, "paste", "Paste the last deleted tasks"),
+        (Action::Refresh, "refresh", "Sync with the remote now"),
+        (Action::FixIds, "fix_ids", "Give every task a new id"),
+        (Action::Insert, "insert", "Add a task"),
+        (
+            Action::AddSubtask,
+            "add_subtask",
+            "Add a subtask to the selected task",
+        ),
+        (Action::Command, "command", "Enter a command"),
+        (Action::Search, "search", "Search tasks"),
+        (
+            Action::SearchNext,
+            "search_next",
+            "Jump to the next search match",
+        ),
+        (
+            Action::SearchPrev,
+            "search_prev",
+            "Jump to the previous search match",
+        ),
+        (Action::Up, "up", "Move up"),
+        (Action::Down, "down", "Move down"),
+        (
+            Action::Top,
+            "top",
+            "Go to the first task, or the task at the count",
+        ),
+        (
+            Action::Bottom,
+            "bottom",
+            "Go to the last task, or the task at the count",
+        ),
+        (Action::HalfPageUp, "half_page_up", "Move up half a page"),
+        (
+            Action::HalfPageDown,
+            "half_page_down",
+            "Move down half a page",
+        ),
+        (Action::PageUp, "page_up", "Move up a page"),
+        (Action::PageDown, "page_down", "Move down a page"),
+        (
+            Action::PriorityUp,
+            "priority_up",
+            "Raise the priority of the selected task",
+        ),
+        (
+            Action::PriorityDown,
+            "priority_down",
+            "Lower the priority of the selected task",
+        ),
+        (
+            Action::Collapse,
+            "collapse",
+            "Collapse the subtasks of the selected task",
+        ),
+        (
+            Action::Expand,
+            "expand",
+            "Expand the subtasks of the selected task",
+        ),
+        (
+            Action::ToggleDetails,
+            "toggle_details",
+            "Toggle the detail pane",
+        ),
+        (
+            Action::EditNotes,
+            "edit_notes",
+            "Edit the notes of the selected task in $EDITOR",
+        ),
+        (Action::Undo, "undo", "Undo the last edit"),
+        (Action::Redo, "redo", "Redo the last undone edit"),
+        (
+            Action::Clear,
+            "clear",
+            "Clear the input and go to the first task",
+        ),
+        (Action::Help, "help", "Show this help"),
+    ];
+
+    /// What the action does, as shown in the help
+    pub fn help(&self) -> &'static str {
+        Action::ALL
+            .iter()
+            .find(|(action, _, _)| action == self)
+            .map(|(_, _, help)| *help)
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = Action::ALL
+            .iter()
+            .find(|(action, _, _)| action == self)
+            .map(|(_, name, _)| *name)
+            .unwrap_or_default();
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Action {
+    type Err = KeymapError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Action::ALL
+            .iter()
+            .find(|(_, name, _)| *name == s)
+            .map(|(action, _, _)| *action)
+            .ok_or_else(|| KeymapError::UnknownAction(s.to_string()))
+    }
+}
+
+/// Why the `keybindings` section of the config was rejected
+#[derive(Debug, PartialEq)]
+pub enum KeymapError {
+    InvalidKey(String),
+    UnknownAction(String),
+    /// Both bindings can never be told apart, one is the start of the other
+    Conflict(String, String),
+    /// Digits typed first are read as a count, so the binding is unreachable
+    LeadingDigit(String),
+    /// Esc leaves every mode before bindings are looked at
+    Reserved(String),
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeymapError::InvalidKey(key) => write!(f, "Invalid key: {}", key),
+            KeymapError::UnknownAction(action) => {
+                write!(f, "Unknown action: {}", action)
+            }
+            KeymapError::Conflict(a, b) => {
+                write!(f, "Conflicting keybindings: \"{}\" and \"{}\"", a, b)
+            }
+            KeymapError::LeadingDigit(keys) => {
+                write!(f, "Keybinding starts with a count: {}", keys)
+            }
+            KeymapError::Reserved(keys) => {
+                write!(f, "Keybinding uses the reserved esc key: {}", keys)
+            }
+        }
+    }
+}
+
+impl std::error::Error for KeymapError {}
+
+/// Shift is part of the character itself, `G` is reported as shift-G
+fn normalize(key: KeyEvent) -> KeyEvent {
+    match key.code {
+        KeyCode::Char(c) => {
+            let c = if key.modifiers.contains(KeyModifiers::SHIFT) {
+                let mut upper = c.to_uppercase();
+                match (upper.next(), upper.next()) {
+                    (Some(upper), None) => upper,
+                    _ => c,
+                }
+            } else {
+                c
+            };
+            KeyEvent::new(KeyCode::Char(c), key.modifiers - KeyModifiers::SHIFT)
+        }
+        _ => key,
+    }
+}
+
+/// Parses a single key like `j`, `ctrl-f`, `pagedown` or `alt-enter`
+fn parse_key(key: &str) -> Result<KeyEvent, KeymapError> {
+    let invalid = || KeymapError::InvalidKey(key.to_string());
+
+    let mut modifiers = KeyModifiers::empty();
+    let mut rest = key;
+    // A lone `-` is a key, not a separator
+    while let Some((modifier, tail)) = rest.split_once('-') {
+        if tail.is_empty() {
+            break;
+        }
+        modifiers |= match modifier.to_lowercase().as_str() {
+            "ctrl" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return Err(invalid()),
+        };
+        rest = tail;
+    }
+
+    let mut chars = rest.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ => match rest.to_lowercase().as_str() {
+            "enter" | "return" => KeyCode::Enter,
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "esc" => KeyCode::Esc,
+            "space" => KeyCode::Char(' '),
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "insert" => KeyCode::Ins,
+            "delete" => KeyCode::Delete,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            name => match name.strip_prefix('f').map(|n| n.parse::<u8>()) {
+                Some(Ok(n)) if (1..=12).contains(&n) => KeyCode::F(n),
+                _ => return Err(invalid()),
+            },
+        },
+    };
+
+    Ok(normalize(KeyEvent::new(code, modifiers)))
+}
+
+fn format_key(key: &KeyEvent) -> String {
+    let mut text = String::new();
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        text.push_str("ctrl-");
+    }
+    if key.modifiers.contains(KeyModifiers::ALT) {
+        text.push_str("alt-");
+    }
+    if key.modifiers.contains(KeyModifiers::SHIFT) {
+        text.push_str("shift-");
+    }
+
+    match key.code {
+        KeyCode::Char(' ') => text.push_str("space"),
+        KeyCode::Char(c) => text.push(c),
+        KeyCode::F(n) => text.push_str(&format!("f{}", n)),
+        code => text.push_str(match code {
+            KeyCode::Enter => "enter",
+            KeyCode::Tab => "tab",
+            KeyCode::Backspace => "backspace",
+            KeyCode::Esc => "esc",
+            KeyCode::Left => "left",
+            KeyCode::Right => "right",
+            KeyCode::Up => "up",
+            KeyCode::Down => "down",
+            KeyCode::Ins => "insert",
+            KeyCode::Delete => "delete",
+            KeyCode::Home => "home",
+            KeyCode::End => "end",
+            KeyCode::PageUp => "pageup",
+            KeyCode::PageDown => "pagedown",
+            _ => "?",
+        }),
+    }
+    text
+}
+
+/// Formats keys the way `parse_key_sequence` reads them
+pub fn format_key_sequence(keys: &[KeyEvent]) -> String {
+    keys.iter()
+        .map(format_key)
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Parses space separated keys like `g g` into the sequence to press
+pub fn parse_key_sequence(keys: &str) -> Result<Vec<KeyEvent>, KeymapError> {
+    let sequence = keys
+        .split_whitespace()
+        .map(parse_key)
+        .collect::<Result<Vec<KeyEvent>, KeymapError>>()?;
+
+    match sequence.first() {
+        None => Err(KeymapError::InvalidKey(keys.to_string())),
+        Some(KeyEvent {
+            code: KeyCode::Char(c),
+            modifiers,
+        }) if c.is_ascii_digit()
+            && !modifiers.contains(KeyModifiers::CONTROL) =>
+        {
+            Err(KeymapError::LeadingDigit(keys.to_string()))
+        }
+        Some(_) => Ok(sequence),
+    }
+}
+
+/// Key sequences of every Global mode action
+pub struct Keymap {
+    bindings: Vec<(Vec<KeyEvent>, Action)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let defaults = [
+            ("s", Action::StartTask),
+            ("f", Action::FinishTask),
+            ("w", Action::WontFix),
+            ("d d", Action::Delete),
+            ("p", Action::Paste),
+            ("r", Action::Refresh),
+            ("x", Action::FixIds),
+            ("i", Action::Insert),
+            ("A", Action::AddSubtask),
+            (":", Action::Command),
+            ("/", Action::Search),
+            ("n", Action::SearchNext),
+            ("N", Action::SearchPrev),
+            ("k", Action::Up),
+            ("up", Action::Up),
+            ("j", Action::Down),
+            ("down", Action::Down),
+            ("g g", Action::Top),
+            ("home", Action::Top),
+            ("G", Action::Bottom),
+            ("end", Action::Bottom),
+            ("ctrl-u", Action::HalfPageUp),
+            ("ctrl-d", Action::HalfPageDown),
+            ("pageup", Action::PageUp),
+            ("pagedown", Action::PageDown),
+            ("+", Action::PriorityUp),
+            ("-", Action::PriorityDown),
+            ("h", Action::Collapse),
+            ("l", Action::Expand),
+            ("v", Action::ToggleDetails),
+            ("e", Action::EditNotes),
+            ("u", Action::Undo),
+            ("ctrl-r", Action::Redo),
+            ("c", Action::Clear),
+            ("?", Action::Help),
+        ];
+
+        Keymap {
+            bindings: defaults
+                .iter()
+                .map(|(keys, action)| {
+                    (parse_key_sequence(keys).unwrap(), *action)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Keymap {
+    /// The default bindings with the `keybindings` from the config on top.
+    /// A configured key replaces any default it would clash with.
+    pub fn new(
+        keybindings: &BTreeMap<String, String>,
+    ) -> Result<Keymap, KeymapError> {
+        let mut custom: Vec<(&str, Vec<KeyEvent>, Action)> = Vec::new();
+        for (keys, action) in keybindings {
+            let sequence = parse_key_sequence(keys)?;
+            if sequence.iter().any(|key| key.code == KeyCode::Esc) {
+                return Err(KeymapError::Reserved(keys.to_string()));
+            }
+            let action = action.parse::<Action>()?;
+
+            if let Some((other, _, _)) = custom
+                .iter()
+                .find(|(_, other, _)| is_ambiguous(&sequence, other))
+            {
+                return Err(KeymapError::Conflict(
+                    other.to_string(),
+                    keys.to_string(),
+                ));
+            }
+            custom.push((keys, sequence, action));
+        }
+
+        let mut keymap = Keymap::default();
+        keymap.bindings.retain(|(sequence, _)| {
+            !custom
+                .iter()
+                .any(|(_, other, _)| is_ambiguous(sequence, other))
+        });
+        keymap.bindings.extend(
+            custom
+                .into_iter()
+                .map(|(_, sequence, action)| (sequence, action)),
+        );
+        Ok(keymap)
+    }
+
+    /// Keys bound to every action in the order of `Action`, for the help.
+    /// Esc comes last, it cannot be rebound.
+    pub fn help(&self) -> Vec<(String, &'static str)> {
+        let esc = (
+            "esc".to_string(),
+            "Leave the current mode and go to the first task",
+        );
+        Action::ALL
+            .iter()
+            .filter_map(|(action, _, _)| {
+                let keys = self
+                    .bindings
+                    .iter()
+                    .filter(|(_, a)| a == action)
+                    .map(|(sequence, _)| format_key_sequence(sequence))
+                    .collect::<Vec<String>>();
+                if keys.is_empty() {
+                    None
+                } else {
+                    Some((keys.join(", "), action.help()))
+                }
+            })
+            .chain(std::iter::once(esc))
+            .collect()
+    }
+
+    /// Action bound to exactly `keys`
+    pub fn get(&self, keys: &[KeyEvent]) -> Option<Action> {
+        let keys = keys.iter().copied().map(normalize).collect::<Vec<_>>();
+        self.bindings
+            .iter()
+            .find(|(sequence, _)| *sequence == keys)
+            .map(|(_, action)| *action)
+    }
+
+    /// Whether `keys` only start a longer binding, like the first `g` of `g g`
+    pub fn is_prefix(&self, keys: &[KeyEvent]) -> bool {
+        let keys = keys.iter().copied().map(normalize).collect::<Vec<_>>();
+        self.bindings.iter().any(|(sequence, _)| {
+            sequence.len() > keys.len() && sequence.starts_with(&keys)
+        })
+    }
+}
+
+/// One sequence is the start of the other so the shorter would always win
+fn is_ambiguous(a: &[KeyEvent], b: &[KeyEvent]) -> bool {
+    a.starts_with(b) || b.starts_with(a)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    fn bindings(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(keys, action)| (keys.to_string(), action.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_keys_with_modifiers() {
+        let none = KeyModifiers::empty();
+        assert_eq!(parse_key("j"), Ok(key(KeyCode::Char('j'), none)));
+        assert_eq!(parse_key("-"), Ok(key(KeyCode::Char('-'), none)));
+        assert_eq!(parse_key("space"), Ok(key(KeyCode::Char(' '), none)));
+        assert_eq!(parse_key("PageDown"), Ok(key(KeyCode::PageDown, none)));
+        assert_eq!(parse_key("f12"), Ok(key(KeyCode::F(12), none)));
+        assert_eq!(
+            parse_key("ctrl-f"),
+            Ok(key(KeyCode::Char('f'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            parse_key("alt-enter"),
+            Ok(key(KeyCode::Enter, KeyModifiers::ALT))
+        );
+        assert_eq!(
+            parse_key("ctrl--"),
+            Ok(key(KeyCode::Char('-'), KeyModifiers::CONTROL))
+        );
+
+        assert!(parse_key("f13").is_err());
+        assert!(parse_key("meta-x").is_err());
+        assert!(parse_key("jk").is_err());
+    }
+
+    #[test]
+    fn shift_is_part_of_the_character() {
+        let upper_a = key(KeyCode::Char('A'), KeyModifiers::empty());
+        assert_eq!(parse_key("shift-a"), Ok(upper_a));
+        assert_eq!(parse_key("A"), Ok(upper_a));
+        assert_eq!(
+            parse_key("shift-tab"),
+            Ok(key(KeyCode::Tab, KeyModifiers::SHIFT))
+        );
+
+        let keymap = Keymap::default();
+        let shift_n = key(KeyCode::Char('n'), KeyModifiers::SHIFT);
+        let shifted_n = key(KeyCode::Char('N'), KeyModifiers::SHIFT);
+        assert_eq!(keymap.get(&[shift_n]), Some(Action::SearchPrev));
+        assert_eq!(keymap.get(&[shifted_n]), Some(Action::SearchPrev));
+    }
+
+    #[test]
+    fn bindings_cannot_start_with_a_count() {
+        assert_eq!(
+            parse_key_sequence("3 x"),
+            Err(KeymapError::LeadingDigit("3 x".to_string()))
+        );
+        assert!(parse_key_sequence("alt-0").is_err());
+        assert!(parse_key_sequence("ctrl-1").is_ok());
+        assert!(parse_key_sequence("g 1").is_ok());
+        assert!(parse_key_sequence("").is_err());
+    }
+
+    #[test]
+    fn custom_bindings_replace_clashing_defaults() {
+        let keymap =
+            Keymap::new(&bindings(&[("g", "top"), ("ctrl-j", "down")]))
+                .unwrap();
+        let g = key(KeyCode::Char('g'), KeyModifiers::empty());
+
+        assert_eq!(keymap.get(&[g]), Some(Action::Top));
+        // `g g` started with the new binding so it is gone
+        assert!(!keymap.is_prefix(&[g]));
+        assert_eq!(
+            keymap.get(&[key(KeyCode::Char('j'), KeyModifiers::CONTROL)]),
+            Some(Action::Down)
+        );
+        assert_eq!(
+            keymap.get(&[key(KeyCode::Char('j'), KeyModifiers::empty())]),
+            Some(Action::Down)
+        );
+    }
+
+    #[test]
+    fn prefixes_wait_for_more_keys() {
+        let keymap = Keymap::default();
+        let g = key(KeyCode::Char('g'), KeyModifiers::empty());
+
+        assert!(keymap.is_prefix(&[g]));
+        assert_eq!(keymap.get(&[g]), None);
+        assert!(!keymap.is_prefix(&[g, g]));
+        assert_eq!(keymap.get(&[g, g]), Some(Action::Top));
+    }
+
+    #[test]
+    fn rejects_bad_custom_bindings() {
+        assert_eq!(
+            Keymap::new(&bindings(&[("g", "top"), ("g t", "bottom")])).err(),
+            Some(KeymapError::Conflict("g".to_string(), "g t".to_string()))
+        );
+        assert_eq!(
+            Keymap::new(&bindings(&[("q", "quit")])).err(),
+            Some(KeymapError::UnknownAction("quit".to_string()))
+        );
+        assert_eq!(
+            Keymap::new(&bindings(&[("hyper-q", "help")])).err(),
+            Some(KeymapError::InvalidKey("hyper-q".to_string()))
+        );
+        assert_eq!(
+            Keymap::new(&bindings(&[("g esc", "top")])).err(),
+            Some(KeymapError::Reserved("g esc".to_string()))
+        );
+    }
+
+    #[test]
+    fn clear_can_be_rebound() {
+        let keymap =
+            Keymap::new(&bindings(&[("c", "undo"), ("ctrl-l", "clear")]))
+                .unwrap();
+        let c = key(KeyCode::Char('c'), KeyModifiers::empty());
+
+        assert_eq!(keymap.get(&[c]), Some(Action::Undo));
+        assert_eq!(
+            keymap.get(&[key(KeyCode::Char('l'), KeyModifiers::CONTROL)]),
+            Some(Action::Clear)
+        );
+        let help = keymap.help();
+        assert!(help.contains(&("ctrl-l".to_string(), Action::Clear.help())));
+        assert_eq!(help.last().map(|(keys, _)| keys.as_str()), Some("esc"));
+    }
+
+    #[test]
+    fn loads_bindings_from_the_config() {
+        let config: crate::config::ClientConfig = serde_yaml::from_str(
+            "client_id: ''\n\
+             client_secret: ''\n\
+             keybindings:\n  \"shift-x\": finish_task\n  \"g t\": top\n",
+        )
+        .unwrap();
+        let keymap = Keymap::new(&config.keybindings.unwrap()).unwrap();
+        let g = key(KeyCode::Char('g'), KeyModifiers::empty());
+        let t = key(KeyCode::Char('t'), KeyModifiers::empty());
+
+        assert_eq!(
+            keymap.get(&[key(KeyCode::Char('X'), KeyModifiers::SHIFT)]),
+            Some(Action::FinishTask)
+        );
+        assert_eq!(keymap.get(&[g, t]), Some(Action::Top));
+        assert_eq!(keymap.get(&[g, g]), Some(Action::Top));
+    }
+}
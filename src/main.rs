@@ -1,17 +1,24 @@
 mod app;
+mod cli;
+mod command;
 mod config;
 mod event;
 mod gist;
+#[cfg(test)]
+mod harness;
+mod keymap;
+mod merge;
+mod storage;
 mod ui;
 
-use crate::event::KeyCode;
 use anyhow::Result;
-use app::{App, AppMode, VimCommand, VimCommandBarResult, WorkItem};
+use app::{App, AppAction};
 use backtrace::Backtrace;
 use clap::App as ClapApp;
 use config::ClientConfig;
-use event::{EventIterator, KeyModifiers};
+use event::{Event, EventIterator, Events};
 use std::error::Error;
+use uuid::Uuid;
 
 use crossterm::{
     event::{DisableMouseCapture, EnableMouseCapture},
@@ -23,8 +30,11 @@ use crossterm::{
     },
 };
 use std::{
+    env,
+    fs::{self, OpenOptions},
     io::{self, stdout, Write},
-    panic::{self, PanicInfo},
+    panic::{self, PanicHookInfo},
+    process::Command,
 };
 use ratatui::{
     backend::{Backend, CrosstermBackend},
@@ -32,6 +42,15 @@ use ratatui::{
 };
 extern crate serde_json;
 
+fn enter_application(mouse: bool) -> Result<()> {
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    if mouse {
+        execute!(io::stdout(), EnableMouseCapture)?;
+    }
+    Ok(())
+}
+
 fn close_application() -> Result<()> {
     disable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -39,46 +58,45 @@ fn close_application() -> Result<()> {
     Ok(())
 }
 
-pub fn on_down_press_handler<T>(
-    selection_data: &[T],
-    selection_index: Option<usize>,
-) -> usize {
-    match selection_index {
-        Some(selection_index) => {
-            if !selection_data.is_empty() {
-                let next_index = selection_index + 1;
-                if next_index > selection_data.len() - 1 {
-                    return 0;
-                } else {
-                    return next_index;
-                }
-            }
-            0
-        }
-        None => 0,
-    }
-}
-
-pub fn on_up_press_handler<T>(
-    selection_data: &[T],
-    selection_index: Option<usize>,
-) -> usize {
-    match selection_index {
-        Some(selection_index) => {
-            if !selection_data.is_empty() {
-                if selection_index > 0 {
-                    return selection_index - 1;
-                } else {
-                    return selection_data.len() - 1;
-                }
-            }
-            0
-        }
-        None => 0,
+/// Suspends the UI and lets the user edit `text` in $VISUAL or $EDITOR
+fn edit_in_editor<E: EventIterator>(
+    events: &mut E,
+    mouse: bool,
+    text: &str,
+) -> Result<String> {
+    // A fresh file that nobody could have put in place beforehand
+    let path =
+        env::temp_dir().join(format!("barnacle-{}.md", Uuid::new_v4()));
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)?
+        .write_all(text.as_bytes())?;
+
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+
+    // The editor reads the terminal itself
+    events.suspend();
+    close_application()?;
+    let status = Command::new(program).args(parts).arg(&path).status();
+    enter_application(mouse)?;
+    events.resume();
+
+    let status = status?;
+    let edited = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+
+    if !status.success() {
+        return Err(anyhow::anyhow!("{} exited with {}", program, status));
     }
+    Ok(edited?)
 }
 
-fn panic_hook(info: &PanicInfo<'_>) {
+fn panic_hook(info: &PanicHookInfo<'_>) {
     if cfg!(debug_assertions) {
         let location = info.location().unwrap();
 
@@ -113,7 +131,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
         panic_hook(info);
     }));
 
-    ClapApp::new(env!("CARGO_PKG_NAME"))
+    let matches = ClapApp::new(env!("CARGO_PKG_NAME"))
         .version(env!("CARGO_PKG_VERSION"))
         .author(env!("CARGO_PKG_AUTHORS"))
         .about(env!("CARGO_PKG_DESCRIPTION"))
@@ -121,38 +139,66 @@ async fn main() -> Result<(), Box<dyn Error>> {
         .after_help(
             "Your Github Gist ID and Client Secret are stored in $HOME/.config/barnacle/client.yml",
         )
+        .subcommands(cli::subcommands())
         .get_matches();
 
     let mut client_config = ClientConfig::new();
     client_config.load_config()?;
 
-    let mut stdout = stdout();
+    if matches.subcommand_name().is_some() {
+        let app = App::new(client_config)?;
+        cli::run(app, &matches).await?;
+        return Ok(());
+    }
+
     if !is_raw_mode_enabled()? {
-        enable_raw_mode()?;
-        crossterm::execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+        enter_application(client_config.mouse_enabled())?;
     }
 
-    let backend = CrosstermBackend::new(stdout);
+    let backend = CrosstermBackend::new(stdout());
     let mut terminal = Terminal::new(backend)?;
     terminal.show_cursor()?;
 
-    let mut events = event::get_events();
+    let mut events = Events::new(event::TICK_RATE);
 
-    let mut app = App::new();
-    app.client_config = client_config;
+    let mut app = App::new(client_config)?;
+
+    let sync_events = events.sender();
+    app.sync_queue.set_listener(Box::new(move |result| {
+        let event = match result {
+            Ok(()) => Event::SyncCompleted,
+            Err(e) => Event::SyncFailed(e.to_string()),
+        };
+        let _ = sync_events.send(event);
+    }));
 
-    app.init().await;
-    app.sync().await;
+    app.init();
 
     terminal.clear()?;
 
+    let result = run_app(&mut terminal, &mut app, &mut events).await;
+    close_application()?;
+    Ok(result?)
+}
+
+/// Draws `app` and handles its events until it quits or `events` runs out
+async fn run_app<B, E>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    events: &mut E,
+) -> Result<()>
+where
+    B: Backend,
+    E: EventIterator,
+{
     loop {
         let parent_layout = Layout::default()
             .direction(Direction::Vertical)
             .constraints(
                 [
                     Constraint::Min(1), 
-                    Constraint::Length(3)
+                    Constraint::Length(3),
+                    Constraint::Length(1),
                 ].as_ref())
             .margin(0);
 
@@ -160,183 +206,41 @@ async fn main() -> Result<(), Box<dyn Error>> {
             app.size = size;
         };
 
-        terminal.draw(|mut f| {
-            ui::draw_core_layout(&mut f, &app, &parent_layout);
+        app.poll_sync();
+
+        terminal.draw(|f| {
+            ui::draw_core_layout(f, app, &parent_layout);
         })?;
 
         io::stdout().flush().ok();
 
-        let mut current_view = app.get_view();
-        current_view.sort_by(|a, b| a.status.partial_cmp(&b.status).unwrap());
-
-        let keyEvent = events.next_event()?;
-        let _keyHandles = match keyEvent.code {
-            KeyCode::Char('d') if keyEvent.modifiers.contains(KeyModifiers::CONTROL) => {
-                {
-                    close_application()?;
-                    break;
-                }
-            },
-            KeyCode::Char('c') => if let AppMode::Global = app.mode {
-                app.insert_bar.clear();
-                app.command_bar.clear();
-                app.mode = AppMode::Global;
-                terminal.hide_cursor()?;
-                app.selected_index = 0;
-                continue;
-            },
-            KeyCode::Esc => {
-                app.insert_bar.clear();
-                app.command_bar.clear();
-                app.mode = AppMode::Global;
-                terminal.hide_cursor()?;
-                app.selected_index = 0;
+        // Ticks, resizes and syncs only need the redraw above
+        let key_event = match events.next_event().await {
+            Some(Event::Key(key_event)) => key_event,
+            Some(Event::Mouse(mouse_event)) => {
+                app.handle_mouse(mouse_event);
                 continue;
             }
-            _=>{}
+            Some(_) => continue,
+            None => break,
         };
 
-        match app.mode {
-            AppMode::Global => match keyEvent.code {
-                KeyCode::Char('s') => {
-                    if let Some(w) =
-                        current_view.get_mut(app.selected_index)
-                    {
-                        app.start_task(&w.id.as_ref().unwrap())
-                    }
-                },
-                KeyCode::Char('x') => {
-                    app.fix_all_work_items();
-                }
-                KeyCode::Char('f') => {
-                    if let Some(w) =
-                        current_view.get_mut(app.selected_index)
-                    {
-                        app.finish_task(&w.id.as_ref().unwrap())
-                    }
-                }
-                KeyCode::Char('w') => {
-                    if let Some(w) =
-                        current_view.get_mut(app.selected_index)
-                    {
-                        app.wont_task(&w.id.as_ref().unwrap())
+        match app.handle_key(key_event).await {
+            AppAction::Redraw => {}
+            AppAction::HideCursor => terminal.hide_cursor()?,
+            AppAction::EditNotes { id, notes } => {
+                let mouse = app.client_config.mouse_enabled();
+                match edit_in_editor(events, mouse, &notes) {
+                    Ok(notes) => app.set_task_notes(&id, &notes),
+                    Err(e) => {
+                        app.status_message =
+                            Some(format!("Could not edit notes: {}", e))
                     }
                 }
-                KeyCode::Char('d') => {
-                    if let Some(w) =
-                        current_view.get_mut(app.selected_index)
-                    {
-                        app.remove_task(&w.id.as_ref().unwrap())
-                    }
-                }
-                KeyCode::Char('p') => {
-                    if app.register.is_some() {
-                        app.add_task(
-                            app.register.as_ref().unwrap().clone(),
-                        );
-                    }
-                }
-                KeyCode::Char('r') => {
-                    app.sync().await;
-                }
-                KeyCode::Char('i') => app.mode = AppMode::Insert,
-                KeyCode::Char(':') => {
-                    app.mode = AppMode::Command;
-                    app.command_bar.handle_input(keyEvent);
-                }
-                KeyCode::Up | KeyCode::Char('k') => {
-                    let next_index = on_up_press_handler(
-                        &app.tasks,
-                        Some(app.selected_index),
-                    );
-                    app.selected_index = next_index;
-                }
-                KeyCode::Down | KeyCode::Char('j') => {
-                    let next_index = on_down_press_handler(
-                        &app.tasks,
-                        Some(app.selected_index),
-                    );
-                    app.selected_index = next_index;
-                }
-                _=>{}
+                terminal.clear()?;
             }
-            AppMode::Insert => match app.insert_bar.handle_input(keyEvent) {
-                VimCommandBarResult::Finished(task) => {
-                    let mut work_item = WorkItem::new();
-                    work_item.content = Some(task);
-                    app.add_task(work_item);
-                }
-                VimCommandBarResult::Aborted => {
-                    app.mode = AppMode::Global
-                }
-                _ => {}
-            },
-            AppMode::Command => {
-                match app.command_bar.handle_input(keyEvent) {
-                    VimCommandBarResult::Finished(cmd) => {
-                        let c = VimCommand::from(cmd);
-                        match c {
-                            VimCommand::Quit => {
-                                close_application()?;
-                                break;
-                            }
-                            VimCommand::ProjectSaveAndQuit => {
-                                app.save_project(true, false);
-                                close_application()?;
-                                break;
-                            }
-                            VimCommand::ProjectSave => {
-                                app.save_project(false, false);
-                            }
-                            VimCommand::TaskRename(index, content) => {
-                                if let Some(w) =
-                                    current_view.get_mut(index)
-                                {
-                                    app.update_work_item_text(
-                                        &w.id.as_ref().unwrap(),
-                                        &content,
-                                    );
-                                }
-                            }
-                            VimCommand::TaskDelete(index) => {
-                                if let Some(w) =
-                                    current_view.get_mut(index)
-                                {
-                                    app.remove_task(
-                                        &w.id.as_ref().unwrap(),
-                                    )
-                                }
-                            }
-                            VimCommand::ProjectNew(name) => {
-                                app.new_project(&name).await;
-                            }
-                            VimCommand::ProjectOpen(name) => {
-                                app.select_project(&name).await;
-                                app.sync().await;
-                            }
-                            VimCommand::ShowFinished(value) => {
-                                app.client_config.show_finished =
-                                    Some(value);
-                                let _ = app.client_config.save_config();
-                            }
-                            VimCommand::ShowToday(value) => {
-                                app.client_config.show_today =
-                                    Some(value);
-                                let _ = app.client_config.save_config();
-                            }
-
-                            VimCommand::TaskSetPriority(_, _) => {}
-                            VimCommand::None => {}
-                        };
-                        app.mode = AppMode::Global
-                    }
-                    VimCommandBarResult::Aborted => {
-                        app.mode = AppMode::Global
-                    }
-                _ => {}
-                }
-            }
-        };
+            AppAction::Quit => break,
+        }
     }
 
     Ok(())
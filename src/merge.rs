@@ -0,0 +1,408 @@
+use super::app::WorkItem;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Parts of a `WorkItem` that are merged independently of each other
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MergeField {
+    /// The item was deleted on one side and changed on the other
+    Item,
+    Content,
+    Priority,
+    Tags,
+    Due,
+    Parent,
+    Notes,
+    /// The status along with the started and finished times, since starting
+    /// or finishing a task always changes them together
+    Status,
+}
+
+impl MergeField {
+    const FIELDS: [MergeField; 7] = [
+        MergeField::Content,
+        MergeField::Priority,
+        MergeField::Tags,
+        MergeField::Due,
+        MergeField::Parent,
+        MergeField::Notes,
+        MergeField::Status,
+    ];
+
+    fn matches(self, a: &WorkItem, b: &WorkItem) -> bool {
+        match self {
+            MergeField::Item => a == b,
+            MergeField::Content => a.content == b.content,
+            MergeField::Priority => a.priority == b.priority,
+            MergeField::Tags => a.tags == b.tags,
+            MergeField::Due => a.due == b.due,
+            MergeField::Parent => a.parent_id == b.parent_id,
+            MergeField::Notes => a.notes == b.notes,
+            MergeField::Status => {
+                a.status == b.status
+                    && a.started_time == b.started_time
+                    && a.finished_time == b.finished_time
+            }
+        }
+    }
+
+    fn copy(self, from: &WorkItem, to: &mut WorkItem) {
+        match self {
+            MergeField::Item => *to = from.clone(),
+            MergeField::Content => to.content = from.content.clone(),
+            MergeField::Priority => to.priority = from.priority,
+            MergeField::Tags => to.tags = from.tags.clone(),
+            MergeField::Due => to.due = from.due,
+            MergeField::Parent => to.parent_id = from.parent_id.clone(),
+            MergeField::Notes => to.notes = from.notes.clone(),
+            MergeField::Status => {
+                to.status = from.status;
+                to.started_time = from.started_time;
+                to.finished_time = from.finished_time;
+            }
+        }
+    }
+
+    /// Human readable value of this field, `deleted` for a missing item
+    pub fn describe(self, item: Option<&WorkItem>) -> String {
+        let item = match item {
+            Some(item) => item,
+            None => return "deleted".to_string(),
+        };
+
+        match self {
+            MergeField::Item | MergeField::Content => {
+                item.content.clone().unwrap_or_default()
+            }
+            MergeField::Priority => item.priority.to_string(),
+            MergeField::Tags => item.tags.join(" "),
+            MergeField::Due => item
+                .due
+                .map(|d| d.format("%Y-%m-%d").to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            MergeField::Parent => {
+                item.parent_id.clone().unwrap_or_else(|| "-".to_string())
+            }
+            MergeField::Notes => item
+                .notes
+                .as_deref()
+                .and_then(|n| n.lines().next())
+                .unwrap_or("-")
+                .to_string(),
+            MergeField::Status => match (item.started_time, item.finished_time)
+            {
+                (_, Some(finished)) => format!(
+                    "{:?} {}",
+                    item.status,
+                    finished.format("%Y-%m-%d %H:%M")
+                ),
+                (Some(started), None) => format!(
+                    "{:?} {}",
+                    item.status,
+                    started.format("%Y-%m-%d %H:%M")
+                ),
+                (None, None) => format!("{:?}", item.status),
+            },
+        }
+    }
+}
+
+impl fmt::Display for MergeField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MergeField::Item => write!(f, "Item"),
+            MergeField::Content => write!(f, "Content"),
+            MergeField::Priority => write!(f, "Priority"),
+            MergeField::Tags => write!(f, "Tags"),
+            MergeField::Due => write!(f, "Due"),
+            MergeField::Parent => write!(f, "Parent"),
+            MergeField::Notes => write!(f, "Notes"),
+            MergeField::Status => write!(f, "Status"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MergeSide {
+    Local,
+    Remote,
+}
+
+/// A field both sides changed in different ways
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conflict {
+    pub id: String,
+    pub field: MergeField,
+    pub local: Option<WorkItem>,
+    pub remote: Option<WorkItem>,
+}
+
+/// Outcome of a three-way merge. Conflicting fields keep the local value in
+/// `tasks` until they are resolved.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MergeResult {
+    pub tasks: Vec<WorkItem>,
+    pub conflicts: Vec<Conflict>,
+}
+
+fn by_id(tasks: &[WorkItem]) -> HashMap<&str, &WorkItem> {
+    tasks
+        .iter()
+        .filter_map(|t| t.id.as_deref().map(|id| (id, t)))
+        .collect()
+}
+
+fn merge_item(
+    id: &str,
+    base: Option<&WorkItem>,
+    local: &WorkItem,
+    remote: &WorkItem,
+    conflicts: &mut Vec<Conflict>,
+) -> WorkItem {
+    let mut merged = local.clone();
+    for field in MergeField::FIELDS.iter() {
+        if field.matches(local, remote) {
+            continue;
+        }
+
+        match base {
+            Some(base) if field.matches(local, base) => {
+                field.copy(remote, &mut merged)
+            }
+            Some(base) if field.matches(remote, base) => {}
+            _ => conflicts.push(Conflict {
+                id: id.to_string(),
+                field: *field,
+                local: Some(local.clone()),
+                remote: Some(remote.clone()),
+            }),
+        }
+    }
+    merged
+}
+
+/// Merges the `local` and `remote` edits of the common ancestor `base`,
+/// matching work items by id
+pub fn merge(
+    base: &[WorkItem],
+    local: &[WorkItem],
+    remote: &[WorkItem],
+) -> MergeResult {
+    let base_items = by_id(base);
+    let local_items = by_id(local);
+    let remote_items = by_id(remote);
+
+    let mut result = MergeResult::default();
+    for item in local {
+        let id = match item.id.as_deref() {
+            Some(id) => id,
+            None => {
+                result.tasks.push(item.clone());
+                continue;
+            }
+        };
+
+        match (base_items.get(id), remote_items.get(id)) {
+            (base, Some(remote)) => result.tasks.push(merge_item(
+                id,
+                base.copied(),
+                item,
+                remote,
+                &mut result.conflicts,
+            )),
+            (None, None) => result.tasks.push(item.clone()),
+            (Some(base), None) => {
+                // Deleted remotely, keep it only if it was changed locally
+                if item != *base {
+                    result.conflicts.push(Conflict {
+                        id: id.to_string(),
+                        field: MergeField::Item,
+                        local: Some(item.clone()),
+                        remote: None,
+                    });
+                    result.tasks.push(item.clone());
+                }
+            }
+        }
+    }
+
+    for item in remote {
+        let id = match item.id.as_deref() {
+            Some(id) if !local_items.contains_key(id) => id,
+            _ => continue,
+        };
+
+        match base_items.get(id) {
+            None => result.tasks.push(item.clone()),
+            Some(base) => {
+                // Deleted locally, keep it only if it was changed remotely
+                if item != *base {
+                    result.conflicts.push(Conflict {
+                        id: id.to_string(),
+                        field: MergeField::Item,
+                        local: None,
+                        remote: Some(item.clone()),
+                    });
+                }
+            }
+        }
+    }
+
+    result
+}
+
+/// Applies the side picked for every conflict to the merged tasks
+pub fn resolve(
+    merged: &[WorkItem],
+    conflicts: &[Conflict],
+    choices: &[MergeSide],
+) -> Vec<WorkItem> {
+    let mut tasks = merged.to_vec();
+    for (conflict, side) in conflicts.iter().zip(choices.iter()) {
+        let chosen = match side {
+            MergeSide::Local => conflict.local.as_ref(),
+            MergeSide::Remote => conflict.remote.as_ref(),
+        };
+        let position = tasks
+            .iter()
+            .position(|t| t.id.as_deref() == Some(conflict.id.as_str()));
+
+        match (conflict.field, chosen, position) {
+            (MergeField::Item, None, Some(position)) => {
+                tasks.remove(position);
+            }
+            (MergeField::Item, Some(item), None) => tasks.push(item.clone()),
+            (field, Some(item), Some(position)) => {
+                field.copy(item, &mut tasks[position])
+            }
+            _ => {}
+        }
+    }
+    tasks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::ItemStatus;
+
+    fn task(id: &str, content: &str) -> WorkItem {
+        let mut item = WorkItem::new();
+        item.id = Some(id.to_string());
+        item.content = Some(content.to_string());
+        item
+    }
+
+    #[test]
+    fn takes_the_side_that_changed() {
+        let base = vec![task("1", "buy milk"), task("2", "walk dog")];
+        let mut local = base.clone();
+        local[0].content = Some("buy oat milk".to_string());
+        let mut remote = base.clone();
+        remote[0].priority = 2;
+        remote[1].tags = vec!["home".to_string()];
+
+        let result = merge(&base, &local, &remote);
+        assert!(result.conflicts.is_empty());
+
+        let mut expected = remote.clone();
+        expected[0].content = Some("buy oat milk".to_string());
+        assert_eq!(result.tasks, expected);
+    }
+
+    #[test]
+    fn same_edit_on_both_sides_is_not_a_conflict() {
+        let base = vec![task("1", "buy milk")];
+        let mut local = base.clone();
+        local[0].content = Some("buy oat milk".to_string());
+        local[0].status = ItemStatus::Started;
+
+        let result = merge(&base, &local, &local);
+        assert!(result.conflicts.is_empty());
+        assert_eq!(result.tasks, local);
+    }
+
+    #[test]
+    fn different_edits_of_a_field_conflict() {
+        let base = vec![task("1", "buy milk")];
+        let mut local = base.clone();
+        local[0].content = Some("buy oat milk".to_string());
+        local[0].priority = 1;
+        let mut remote = base.clone();
+        remote[0].content = Some("buy soy milk".to_string());
+
+        let result = merge(&base, &local, &remote);
+        assert_eq!(result.conflicts.len(), 1);
+        let conflict = &result.conflicts[0];
+        assert_eq!(conflict.field, MergeField::Content);
+        assert_eq!(conflict.local.as_ref(), Some(&local[0]));
+        assert_eq!(conflict.remote.as_ref(), Some(&remote[0]));
+        // Local until resolved
+        assert_eq!(result.tasks, local);
+
+        let tasks =
+            resolve(&result.tasks, &result.conflicts, &[MergeSide::Remote]);
+        assert_eq!(tasks[0].content.as_deref(), Some("buy soy milk"));
+        assert_eq!(tasks[0].priority, 1);
+
+        let tasks =
+            resolve(&result.tasks, &result.conflicts, &[MergeSide::Local]);
+        assert_eq!(tasks, local);
+    }
+
+    #[test]
+    fn added_tasks_are_kept_from_both_sides() {
+        let base = vec![task("1", "buy milk")];
+        let mut local = base.clone();
+        local.push(task("2", "walk dog"));
+        let mut remote = base.clone();
+        remote.push(task("3", "call mom"));
+
+        let result = merge(&base, &local, &remote);
+        assert!(result.conflicts.is_empty());
+        assert_eq!(
+            result.tasks,
+            vec![base[0].clone(), local[1].clone(), remote[1].clone()]
+        );
+    }
+
+    #[test]
+    fn unchanged_tasks_deleted_on_one_side_stay_deleted() {
+        let base = vec![task("1", "buy milk"), task("2", "walk dog")];
+        let local = vec![base[1].clone()];
+        let remote = vec![base[0].clone()];
+
+        let result = merge(&base, &local, &remote);
+        assert!(result.conflicts.is_empty());
+        assert!(result.tasks.is_empty());
+    }
+
+    #[test]
+    fn changed_tasks_deleted_on_the_other_side_conflict() {
+        let base = vec![task("1", "buy milk"), task("2", "walk dog")];
+        let mut local = vec![base[0].clone()];
+        local[0].status = ItemStatus::Finished;
+        let mut remote = vec![base[1].clone()];
+        remote[0].content = Some("walk the dog".to_string());
+
+        let result = merge(&base, &local, &remote);
+        assert_eq!(result.conflicts.len(), 2);
+        assert!(result.conflicts.iter().all(|c| c.field == MergeField::Item));
+        assert_eq!(result.tasks, local);
+
+        // The remote deleted the first task and kept the second one
+        let tasks = resolve(
+            &result.tasks,
+            &result.conflicts,
+            &[MergeSide::Remote, MergeSide::Remote],
+        );
+        assert_eq!(tasks, remote);
+
+        let tasks = resolve(
+            &result.tasks,
+            &result.conflicts,
+            &[MergeSide::Local, MergeSide::Local],
+        );
+        assert_eq!(tasks, local);
+    }
+}
@@ -0,0 +1,252 @@
+use super::{list_project_files, project_file_path, write_atomic};
+use crate::app::WorkItem;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, path::PathBuf, sync::Mutex};
+
+const PROJECTS_DIR: &str = "projects";
+const BASE_DIR: &str = "base";
+const PENDING_FILE_NAME: &str = "pending.json";
+
+/// An upload waiting in the queue
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PendingUpload {
+    /// Bumped on every local edit so uploads of older edits can be told apart
+    pub revision: u64,
+    /// The project was created locally and does not exist on the remote yet
+    #[serde(default)]
+    pub create: bool,
+    /// The project was deleted locally and still has to be removed from the
+    /// remote, before it is created again if `create` is set too
+    #[serde(default)]
+    pub delete: bool,
+}
+
+/// On-disk copy of every project the TUI reads from and writes to, along
+/// with the revisions that still have to be uploaded. The last copy seen on
+/// the remote is kept as the base for three-way merges.
+pub struct ProjectCache {
+    cache_dir: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl ProjectCache {
+    pub fn new(cache_dir: PathBuf) -> Self {
+        ProjectCache {
+            cache_dir,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Names of every cached project
+    pub fn list_projects(&self) -> Result<Vec<String>> {
+        let _guard = self.lock.lock().unwrap();
+        list_project_files(&self.cache_dir.join(PROJECTS_DIR))
+    }
+
+    /// Cached work items of `project`, `None` if it was never cached
+    pub fn load(&self, project: &str) -> Result<Option<Vec<WorkItem>>> {
+        let _guard = self.lock.lock().unwrap();
+        let path = self.project_path(project)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let data = fs::read_to_string(&path)?;
+        Ok(Some(serde_json::from_str(&data)?))
+    }
+
+    /// The remote copy of `project` local edits are based on
+    pub fn load_base(&self, project: &str) -> Result<Option<Vec<WorkItem>>> {
+        let _guard = self.lock.lock().unwrap();
+        let path = self.base_path(project)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let data = fs::read_to_string(&path)?;
+        Ok(Some(serde_json::from_str(&data)?))
+    }
+
+    /// Stores a local edit of `project` and queues it for upload
+    pub fn store_local(&self, project: &str, tasks: &[WorkItem]) -> Result<()> {
+        let _guard = self.lock.lock().unwrap();
+        self.write_project(project, tasks)?;
+
+        let mut pending = self.read_pending()?;
+        pending.entry(project.to_string()).or_default().revision += 1;
+        self.write_pending(&pending)
+    }
+
+    /// Stores a new, empty `project` and queues its creation
+    pub fn create_local(&self, project: &str) -> Result<()> {
+        let _guard = self.lock.lock().unwrap();
+        self.write_project(project, &[])?;
+
+        let mut pending = self.read_pending()?;
+        let upload = pending.entry(project.to_string()).or_default();
+        upload.revision += 1;
+        upload.create = true;
+        self.write_pending(&pending)
+    }
+
+    /// Removes `project` and queues its removal from the remote
+    pub fn delete_local(&self, project: &str) -> Result<()> {
+        let _guard = self.lock.lock().unwrap();
+        self.remove_files(project)?;
+
+        let mut pending = self.read_pending()?;
+        let upload = pending.entry(project.to_string()).or_default();
+        upload.revision += 1;
+        upload.create = false;
+        upload.delete = true;
+        self.write_pending(&pending)
+    }
+
+    /// Records that `project`, deleted locally at `revision`, is gone from
+    /// the remote
+    pub fn mark_deleted(&self, project: &str, revision: u64) -> Result<()> {
+        let _guard = self.lock.lock().unwrap();
+        let mut pending = self.read_pending()?;
+        match pending.get_mut(project) {
+            Some(upload) if upload.revision == revision => {
+                pending.remove(project);
+            }
+            // Created again while deleting, only that is left to upload
+            Some(upload) => upload.delete = false,
+            None => return Ok(()),
+        }
+        self.write_pending(&pending)
+    }
+
+    /// Stores the remote copy of `project` unless it has local edits that
+    /// have not been uploaded yet. Returns whether the cached copy changed.
+    pub fn store_remote(
+        &self,
+        project: &str,
+        tasks: &[WorkItem],
+    ) -> Result<bool> {
+        let _guard = self.lock.lock().unwrap();
+        if self.read_pending()?.contains_key(project) {
+            return Ok(false);
+        }
+
+        self.write_base(project, tasks)?;
+        let path = self.project_path(project)?;
+        let data = serde_json::to_string_pretty(tasks)?;
+        if path.exists() && fs::read_to_string(&path)? == data {
+            return Ok(false);
+        }
+
+        write_atomic(&path, &data)?;
+        Ok(true)
+    }
+
+    /// Records that `uploaded`, built from `local` at `revision`, is now
+    /// the remote copy of `project`. Returns whether the cached copy changed.
+    pub fn mark_uploaded(
+        &self,
+        project: &str,
+        revision: u64,
+        local: &[WorkItem],
+        uploaded: &[WorkItem],
+    ) -> Result<bool> {
+        let _guard = self.lock.lock().unwrap();
+        let mut pending = self.read_pending()?;
+        if pending.get(project).map(|u| u.revision) != Some(revision) {
+            // Edited again while uploading, those edits are based on `local`
+            self.write_base(project, local)?;
+            return Ok(false);
+        }
+
+        self.write_base(project, uploaded)?;
+        pending.remove(project);
+        self.write_pending(&pending)?;
+
+        if local == uploaded {
+            return Ok(false);
+        }
+        self.write_project(project, uploaded)?;
+        Ok(true)
+    }
+
+    /// Stores the outcome of resolving conflicts against `remote` and
+    /// queues it for upload
+    pub fn store_resolved(
+        &self,
+        project: &str,
+        tasks: &[WorkItem],
+        remote: &[WorkItem],
+    ) -> Result<()> {
+        let _guard = self.lock.lock().unwrap();
+        self.write_project(project, tasks)?;
+        self.write_base(project, remote)?;
+
+        let mut pending = self.read_pending()?;
+        pending.entry(project.to_string()).or_default().revision += 1;
+        self.write_pending(&pending)
+    }
+
+    /// Removes `project` and any upload queued for it
+    pub fn remove(&self, project: &str) -> Result<()> {
+        let _guard = self.lock.lock().unwrap();
+        self.remove_files(project)?;
+
+        let mut pending = self.read_pending()?;
+        if pending.remove(project).is_some() {
+            self.write_pending(&pending)?;
+        }
+        Ok(())
+    }
+
+    /// Projects waiting to be uploaded
+    pub fn pending(&self) -> Result<HashMap<String, PendingUpload>> {
+        let _guard = self.lock.lock().unwrap();
+        self.read_pending()
+    }
+
+    fn project_path(&self, project: &str) -> Result<PathBuf> {
+        project_file_path(&self.cache_dir.join(PROJECTS_DIR), project)
+    }
+
+    fn base_path(&self, project: &str) -> Result<PathBuf> {
+        project_file_path(&self.cache_dir.join(BASE_DIR), project)
+    }
+
+    fn remove_files(&self, project: &str) -> Result<()> {
+        for path in [self.project_path(project)?, self.base_path(project)?] {
+            if path.exists() {
+                fs::remove_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_base(&self, project: &str, tasks: &[WorkItem]) -> Result<()> {
+        let path = self.base_path(project)?;
+        write_atomic(&path, &serde_json::to_string_pretty(tasks)?)
+    }
+
+    fn write_project(&self, project: &str, tasks: &[WorkItem]) -> Result<()> {
+        let path = self.project_path(project)?;
+        write_atomic(&path, &serde_json::to_string_pretty(tasks)?)
+    }
+
+    fn read_pending(&self) -> Result<HashMap<String, PendingUpload>> {
+        let path = self.cache_dir.join(PENDING_FILE_NAME);
+        if !path.exists() {
+            return Ok(HashMap::new());
+        }
+
+        let data = fs::read_to_string(&path)?;
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    fn write_pending(
+        &self,
+        pending: &HashMap<String, PendingUpload>,
+    ) -> Result<()> {
+        let path = self.cache_dir.join(PENDING_FILE_NAME);
+        write_atomic(&path, &serde_json::to_string_pretty(pending)?)
+    }
+}
@@ -0,0 +1,117 @@
+use super::Storage;
+use crate::app::WorkItem;
+use crate::gist::{get_gist_file, GistError, GistUpdate, ListGist};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Mutex;
+
+/// Stores every project as a file of a single Github Gist.
+pub struct GistStorage {
+    api_base_url: String,
+    client_id: String,
+    client_secret: String,
+    file_list: Mutex<Option<ListGist>>,
+}
+
+impl GistStorage {
+    pub fn new(
+        api_base_url: String,
+        client_id: String,
+        client_secret: String,
+    ) -> Self {
+        GistStorage {
+            api_base_url,
+            client_id,
+            client_secret,
+            file_list: Mutex::new(None),
+        }
+    }
+
+    async fn get_file_list(&self, refresh: bool) -> Result<ListGist> {
+        if !refresh {
+            if let Some(list) = self.file_list.lock().unwrap().as_ref() {
+                return Ok(list.clone());
+            }
+        }
+
+        let list = ListGist::get_list_for_gist(
+            &self.api_base_url,
+            &self.client_id,
+            &self.client_secret,
+        )
+        .await?;
+        *self.file_list.lock().unwrap() = Some(list.clone());
+        Ok(list)
+    }
+}
+
+#[async_trait]
+impl Storage for GistStorage {
+    async fn list_projects(&self) -> Result<Vec<String>> {
+        let list = self.get_file_list(true).await?;
+        let gist = list.search_gist(&self.client_id)?;
+        Ok(gist
+            .files
+            .values()
+            .map(|file| file.name.clone())
+            .collect::<Vec<String>>())
+    }
+
+    async fn load_project(&self, project: &str) -> Result<Vec<WorkItem>> {
+        let mut list = self.get_file_list(false).await?;
+        if list
+            .get_url_gist_file(self.client_id.as_str(), project)
+            .is_err()
+        {
+            list = self.get_file_list(true).await?;
+        }
+
+        let url = list.get_url_gist_file(self.client_id.as_str(), project)?;
+        let data = get_gist_file(&url, &self.client_secret).await?;
+        Ok(serde_json::from_str(&data).map_err(GistError::from)?)
+    }
+
+    async fn save_project(
+        &self,
+        project: &str,
+        tasks: &[WorkItem],
+    ) -> Result<()> {
+        let list = self.get_file_list(false).await?;
+        let url = list.search_url_gist(&self.client_id)?;
+        let update = GistUpdate::new(
+            serde_json::to_string(tasks)?,
+            project.to_string(),
+            project.to_string(),
+            Some(project.to_string()),
+        );
+
+        update.update(&url, &self.client_secret).await?;
+        // Raw urls are pinned to a revision, the next load needs a new list
+        *self.file_list.lock().unwrap() = None;
+        Ok(())
+    }
+
+    async fn create_project(&self, project: &str) -> Result<()> {
+        let list = self.get_file_list(true).await?;
+        if list
+            .get_url_gist_file(self.client_id.as_str(), project)
+            .is_ok()
+        {
+            return Ok(());
+        }
+
+        self.save_project(project, &[]).await?;
+        self.get_file_list(true).await?;
+        Ok(())
+    }
+
+    async fn delete_project(&self, project: &str) -> Result<()> {
+        let list = self.get_file_list(false).await?;
+        let url = list.search_url_gist(&self.client_id)?;
+        GistUpdate::delete(project.to_string())
+            .update(&url, &self.client_secret)
+            .await?;
+        self.get_file_list(true).await?;
+        Ok(())
+    }
+}
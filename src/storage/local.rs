@@ -0,0 +1,86 @@
+use super::{list_project_files, project_file_path, write_atomic, Storage};
+use crate::app::WorkItem;
+use anyhow::anyhow;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::{fs, path::PathBuf};
+
+/// Stores every project as a JSON file inside a local directory.
+pub struct LocalStorage {
+    projects_dir: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(projects_dir: PathBuf) -> Self {
+        LocalStorage { projects_dir }
+    }
+
+    /// Names of every project in the directory
+    pub fn list(&self) -> Result<Vec<String>> {
+        list_project_files(&self.projects_dir)
+    }
+
+    /// Work items of `project`, `None` if it does not exist
+    pub fn load(&self, project: &str) -> Result<Option<Vec<WorkItem>>> {
+        let path = self.project_path(project)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let data = fs::read_to_string(&path)?;
+        Ok(Some(serde_json::from_str(&data)?))
+    }
+
+    pub fn save(&self, project: &str, tasks: &[WorkItem]) -> Result<()> {
+        let path = self.project_path(project)?;
+        write_atomic(&path, &serde_json::to_string_pretty(tasks)?)
+    }
+
+    pub fn create(&self, project: &str) -> Result<()> {
+        let path = self.project_path(project)?;
+        if path.exists() {
+            return Ok(());
+        }
+        self.save(project, &[])
+    }
+
+    pub fn delete(&self, project: &str) -> Result<()> {
+        let path = self.project_path(project)?;
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+        Ok(())
+    }
+
+    fn project_path(&self, project: &str) -> Result<PathBuf> {
+        project_file_path(&self.projects_dir, project)
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn list_projects(&self) -> Result<Vec<String>> {
+        self.list()
+    }
+
+    async fn load_project(&self, project: &str) -> Result<Vec<WorkItem>> {
+        self.load(project)?
+            .ok_or_else(|| anyhow!("project does not exist: {}", project))
+    }
+
+    async fn save_project(
+        &self,
+        project: &str,
+        tasks: &[WorkItem],
+    ) -> Result<()> {
+        self.save(project, tasks)
+    }
+
+    async fn create_project(&self, project: &str) -> Result<()> {
+        self.create(project)
+    }
+
+    async fn delete_project(&self, project: &str) -> Result<()> {
+        self.delete(project)
+    }
+}
@@ -0,0 +1,54 @@
+use super::Storage;
+use crate::app::WorkItem;
+use anyhow::anyhow;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::{collections::BTreeMap, sync::Mutex};
+
+/// Keeps projects in memory only, for tests
+#[derive(Default)]
+pub struct MemoryStorage {
+    projects: Mutex<BTreeMap<String, Vec<WorkItem>>>,
+}
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    async fn list_projects(&self) -> Result<Vec<String>> {
+        Ok(self.projects.lock().unwrap().keys().cloned().collect())
+    }
+
+    async fn load_project(&self, project: &str) -> Result<Vec<WorkItem>> {
+        self.projects
+            .lock()
+            .unwrap()
+            .get(project)
+            .cloned()
+            .ok_or_else(|| anyhow!("project does not exist: {}", project))
+    }
+
+    async fn save_project(
+        &self,
+        project: &str,
+        tasks: &[WorkItem],
+    ) -> Result<()> {
+        self.projects
+            .lock()
+            .unwrap()
+            .insert(project.to_string(), tasks.to_vec());
+        Ok(())
+    }
+
+    async fn create_project(&self, project: &str) -> Result<()> {
+        self.projects
+            .lock()
+            .unwrap()
+            .entry(project.to_string())
+            .or_default();
+        Ok(())
+    }
+
+    async fn delete_project(&self, project: &str) -> Result<()> {
+        self.projects.lock().unwrap().remove(project);
+        Ok(())
+    }
+}
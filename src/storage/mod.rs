@@ -0,0 +1,116 @@
+use super::app::WorkItem;
+use super::config::{ClientConfig, StorageBackend};
+use anyhow::anyhow;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+mod cache;
+mod gist;
+mod local;
+#[cfg(test)]
+mod memory;
+mod sync;
+
+const PROJECT_EXTENSION: &str = "json";
+
+pub use self::cache::ProjectCache;
+pub use self::gist::GistStorage;
+pub use self::local::LocalStorage;
+#[cfg(test)]
+pub use self::memory::MemoryStorage;
+pub use self::sync::{ProjectConflict, SyncQueue, SyncStatus};
+
+/// A place where projects and their work items are persisted.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Names of every project known to the backend
+    async fn list_projects(&self) -> Result<Vec<String>>;
+
+    /// Loads the work items of `project`
+    async fn load_project(&self, project: &str) -> Result<Vec<WorkItem>>;
+
+    /// Replaces the work items of `project` with `tasks`
+    async fn save_project(
+        &self,
+        project: &str,
+        tasks: &[WorkItem],
+    ) -> Result<()>;
+
+    /// Creates an empty `project`
+    async fn create_project(&self, project: &str) -> Result<()>;
+
+    /// Removes `project` and all of its work items
+    async fn delete_project(&self, project: &str) -> Result<()>;
+}
+
+/// The queue the TUI reads and writes projects through
+pub fn new_sync_queue(config: &ClientConfig) -> Result<SyncQueue> {
+    let paths = config.get_or_build_paths()?;
+    match config.storage.unwrap_or_default() {
+        StorageBackend::Gist => {
+            let remote = Arc::new(GistStorage::new(
+                config.api_base_url(),
+                config.client_id.to_owned(),
+                config.client_secret.to_owned(),
+            ));
+            let cache = ProjectCache::new(paths.cache_dir_path);
+            Ok(SyncQueue::new(remote, cache))
+        }
+        // Already on disk, so there is nothing to cache or upload
+        StorageBackend::Local => {
+            Ok(SyncQueue::local(LocalStorage::new(paths.projects_dir_path)))
+        }
+    }
+}
+
+/// Path of the JSON file holding `project` inside `dir`
+fn project_file_path(dir: &Path, project: &str) -> Result<PathBuf> {
+    let file_name = Path::new(project);
+    if project.is_empty()
+        || file_name.components().count() != 1
+        || file_name.file_name().is_none()
+    {
+        return Err(anyhow!("invalid project name: {}", project));
+    }
+
+    Ok(dir.join(format!("{}.{}", project, PROJECT_EXTENSION)))
+}
+
+/// Names of every project file inside `dir`
+fn list_project_files(dir: &Path) -> Result<Vec<String>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut projects = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some(PROJECT_EXTENSION)
+        {
+            continue;
+        }
+        if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+            projects.push(name.to_string());
+        }
+    }
+
+    projects.sort();
+    Ok(projects)
+}
+
+/// Writes next to `path` first so a crash never leaves half a file behind
+fn write_atomic(path: &Path, data: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, data)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
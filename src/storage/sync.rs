@@ -0,0 +1,650 @@
+use super::cache::PendingUpload;
+use super::{LocalStorage, ProjectCache, Storage};
+use crate::app::WorkItem;
+use crate::gist::GistError;
+use crate::merge::{self, MergeResult};
+use anyhow::Result;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::time::delay_for;
+
+/// How often the background task retries uploads that are still queued
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncStatus {
+    Dirty,
+    Syncing,
+    Synced,
+    Offline,
+    Conflict,
+    Error,
+}
+
+impl fmt::Display for SyncStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SyncStatus::Dirty => write!(f, "dirty"),
+            SyncStatus::Syncing => write!(f, "syncing"),
+            SyncStatus::Synced => write!(f, "synced"),
+            SyncStatus::Offline => write!(f, "offline"),
+            SyncStatus::Conflict => write!(f, "conflict"),
+            SyncStatus::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Called with the outcome of every sync
+pub type SyncListener = Box<dyn Fn(&Result<()>) + Send>;
+
+/// Local edits of a project that could not be merged with the remote copy
+#[derive(Clone, Debug)]
+pub struct ProjectConflict {
+    pub remote: Vec<WorkItem>,
+    pub merged: MergeResult,
+}
+
+/// Where the projects the TUI edits are kept
+enum Store {
+    /// Directly in a local directory, there is nothing to sync with
+    Local(LocalStorage),
+    /// In a cache that uploads are queued from
+    Cached {
+        remote: Arc<dyn Storage>,
+        cache: ProjectCache,
+    },
+}
+
+/// Keeps the local `ProjectCache` and the remote `Storage` in step. Edits
+/// only ever touch the cache; uploads are queued and flushed whenever the
+/// remote can be reached. Local storage is written to directly instead.
+pub struct SyncQueue {
+    store: Store,
+    status: Mutex<SyncStatus>,
+    last_error: Mutex<Option<String>>,
+    updated: Mutex<HashSet<String>>,
+    conflicts: Mutex<HashMap<String, ProjectConflict>>,
+    new_conflicts: Mutex<HashSet<String>>,
+    flush_lock: tokio::sync::Mutex<()>,
+    listener: Mutex<Option<SyncListener>>,
+}
+
+impl SyncQueue {
+    pub fn new(remote: Arc<dyn Storage>, cache: ProjectCache) -> Self {
+        let status = match cache.pending() {
+            Ok(pending) if pending.is_empty() => SyncStatus::Synced,
+            _ => SyncStatus::Dirty,
+        };
+
+        SyncQueue::with_store(Store::Cached { remote, cache }, status)
+    }
+
+    /// Edits go straight to `storage`, pushing and reconciling do nothing
+    pub fn local(storage: LocalStorage) -> Self {
+        SyncQueue::with_store(Store::Local(storage), SyncStatus::Synced)
+    }
+
+    fn with_store(store: Store, status: SyncStatus) -> Self {
+        SyncQueue {
+            store,
+            status: Mutex::new(status),
+            last_error: Mutex::new(None),
+            updated: Mutex::new(HashSet::new()),
+            conflicts: Mutex::new(HashMap::new()),
+            new_conflicts: Mutex::new(HashSet::new()),
+            flush_lock: tokio::sync::Mutex::new(()),
+            listener: Mutex::new(None),
+        }
+    }
+
+    pub fn status(&self) -> SyncStatus {
+        *self.status.lock().unwrap()
+    }
+
+    /// Tells `listener` whenever a push or reconcile finishes
+    pub fn set_listener(&self, listener: SyncListener) {
+        *self.listener.lock().unwrap() = Some(listener);
+    }
+
+    /// Why the last sync failed, cleared once a sync succeeds
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error.lock().unwrap().clone()
+    }
+
+    pub fn list_projects(&self) -> Result<Vec<String>> {
+        match &self.store {
+            Store::Local(storage) => storage.list(),
+            Store::Cached { cache, .. } => cache.list_projects(),
+        }
+    }
+
+    pub fn load(&self, project: &str) -> Result<Option<Vec<WorkItem>>> {
+        match &self.store {
+            Store::Local(storage) => storage.load(project),
+            Store::Cached { cache, .. } => cache.load(project),
+        }
+    }
+
+    /// Writes `tasks` to the cache and queues them for upload
+    pub fn save(&self, project: &str, tasks: &[WorkItem]) -> Result<()> {
+        match &self.store {
+            Store::Local(storage) => storage.save(project, tasks),
+            Store::Cached { cache, .. } => {
+                cache.store_local(project, tasks)?;
+                self.set_status(SyncStatus::Dirty);
+                Ok(())
+            }
+        }
+    }
+
+    /// Creates an empty `project` in the cache and queues its creation
+    pub fn create(&self, project: &str) -> Result<()> {
+        match &self.store {
+            Store::Local(storage) => storage.create(project),
+            Store::Cached { cache, .. } => {
+                cache.create_local(project)?;
+                self.set_status(SyncStatus::Dirty);
+                Ok(())
+            }
+        }
+    }
+
+    /// Removes `project` from the cache and queues its removal
+    pub fn delete(&self, project: &str) -> Result<()> {
+        match &self.store {
+            Store::Local(storage) => storage.delete(project),
+            Store::Cached { cache, .. } => {
+                cache.delete_local(project)?;
+                self.conflicts.lock().unwrap().remove(project);
+                self.set_status(SyncStatus::Dirty);
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns whether the cached copy of `project` was replaced by a newer
+    /// remote copy since the last call
+    pub fn take_updated(&self, project: &str) -> bool {
+        self.updated.lock().unwrap().remove(project)
+    }
+
+    /// Returns whether conflicts were found in `project` since the last call
+    pub fn take_new_conflict(&self, project: &str) -> bool {
+        self.new_conflicts.lock().unwrap().remove(project)
+    }
+
+    /// Conflicts that keep the local edits of `project` from being uploaded
+    pub fn conflict(&self, project: &str) -> Option<ProjectConflict> {
+        self.conflicts.lock().unwrap().get(project).cloned()
+    }
+
+    /// Projects whose local edits are held back by conflicts
+    pub fn conflicted_projects(&self) -> Vec<String> {
+        let mut projects = self
+            .conflicts
+            .lock()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<String>>();
+        projects.sort();
+        projects
+    }
+
+    /// Replaces the conflicting edits of `project` with `tasks`, the result
+    /// of resolving them against `remote`, and queues the upload
+    pub fn resolve(
+        &self,
+        project: &str,
+        tasks: &[WorkItem],
+        remote: &[WorkItem],
+    ) -> Result<()> {
+        match &self.store {
+            Store::Local(storage) => storage.save(project, tasks)?,
+            Store::Cached { cache, .. } => {
+                cache.store_resolved(project, tasks, remote)?
+            }
+        }
+        self.conflicts.lock().unwrap().remove(project);
+        self.set_status(SyncStatus::Dirty);
+        Ok(())
+    }
+
+    /// Uploads every queued project
+    pub async fn push(&self) -> Result<()> {
+        self.set_status(SyncStatus::Syncing);
+        let result = self.flush().await;
+        self.finish(result)
+    }
+
+    /// Uploads every queued project and then refreshes the cache from the
+    /// remote
+    pub async fn reconcile(&self) -> Result<()> {
+        self.set_status(SyncStatus::Syncing);
+        let result = match self.flush().await {
+            Ok(()) => self.pull().await,
+            Err(e) => Err(e),
+        };
+        self.finish(result)
+    }
+
+    /// Reconciles once right away and then keeps retrying in the background
+    /// while uploads are queued or the remote is unreachable
+    pub fn spawn_background(self: &Arc<Self>) {
+        let queue = Arc::clone(self);
+        tokio::spawn(async move {
+            let _ = queue.reconcile().await;
+            loop {
+                delay_for(RETRY_INTERVAL).await;
+                match queue.status() {
+                    SyncStatus::Offline | SyncStatus::Error => {
+                        let _ = queue.reconcile().await;
+                    }
+                    SyncStatus::Dirty => {
+                        let _ = queue.push().await;
+                    }
+                    _ => {}
+                }
+            }
+        });
+    }
+
+    /// Uploads every queued project in the background
+    pub fn spawn_push(self: &Arc<Self>) {
+        let queue = Arc::clone(self);
+        tokio::spawn(async move {
+            let _ = queue.push().await;
+        });
+    }
+
+    async fn flush(&self) -> Result<()> {
+        let (storage, cache) = match &self.store {
+            Store::Local(_) => return Ok(()),
+            Store::Cached { remote, cache } => (remote, cache),
+        };
+
+        let _lock = self.flush_lock.lock().await;
+        let mut failure = None;
+        for (project, upload) in cache.pending()? {
+            match self.flush_project(storage, cache, &project, upload).await {
+                Ok(()) => {}
+                // The other projects would only time out as well
+                Err(e) if is_offline(&e) => return Err(e),
+                // One broken project must not hold back all the others
+                Err(e) => {
+                    failure.get_or_insert(e);
+                }
+            }
+        }
+        failure.map_or(Ok(()), Err)
+    }
+
+    async fn flush_project(
+        &self,
+        storage: &Arc<dyn Storage>,
+        cache: &ProjectCache,
+        project: &str,
+        upload: PendingUpload,
+    ) -> Result<()> {
+        if upload.delete {
+            if storage.list_projects().await?.iter().any(|p| p == project) {
+                storage.delete_project(project).await?;
+            }
+            if !upload.create {
+                return cache.mark_deleted(project, upload.revision);
+            }
+        }
+
+        let local = cache.load(project)?.unwrap_or_default();
+        let (base, remote) =
+            if storage.list_projects().await?.iter().any(|p| p == project) {
+                // Without a base every remote task counts as added remotely,
+                // so tasks both sides have are merged instead of overwritten
+                let base = cache.load_base(project)?.unwrap_or_default();
+                (base, storage.load_project(project).await?)
+            } else {
+                // Never uploaded or deleted elsewhere, either way the local
+                // copy becomes the project
+                storage.create_project(project).await?;
+                (Vec::new(), Vec::new())
+            };
+        let tasks = if remote == base {
+            local.clone()
+        } else {
+            let merged = merge::merge(&base, &local, &remote);
+            if !merged.conflicts.is_empty() {
+                self.add_conflict(project, remote, merged);
+                return Ok(());
+            }
+            merged.tasks
+        };
+
+        storage.save_project(project, &tasks).await?;
+        self.conflicts.lock().unwrap().remove(project);
+        if cache.mark_uploaded(project, upload.revision, &local, &tasks)? {
+            self.updated.lock().unwrap().insert(project.to_string());
+        }
+        Ok(())
+    }
+
+    fn add_conflict(
+        &self,
+        project: &str,
+        remote: Vec<WorkItem>,
+        merged: MergeResult,
+    ) {
+        let conflict = ProjectConflict { remote, merged };
+        let mut conflicts = self.conflicts.lock().unwrap();
+        let known = conflicts
+            .get(project)
+            .map(|c| c.merged == conflict.merged)
+            .unwrap_or(false);
+
+        if !known {
+            conflicts.insert(project.to_string(), conflict);
+            self.new_conflicts
+                .lock()
+                .unwrap()
+                .insert(project.to_string());
+        }
+    }
+
+    async fn pull(&self) -> Result<()> {
+        let (remote, cache) = match &self.store {
+            Store::Local(_) => return Ok(()),
+            Store::Cached { remote, cache } => (remote, cache),
+        };
+
+        let remote_projects = remote.list_projects().await?;
+        for project in remote_projects.iter() {
+            let tasks = remote.load_project(project).await?;
+            if cache.store_remote(project, &tasks)? {
+                self.updated.lock().unwrap().insert(project.to_string());
+            }
+        }
+
+        // Projects deleted elsewhere, unless they only exist locally so far
+        let pending = cache.pending()?;
+        for project in cache.list_projects()? {
+            if !remote_projects.contains(&project)
+                && !pending.contains_key(&project)
+            {
+                cache.remove(&project)?;
+                self.updated.lock().unwrap().insert(project);
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&self, result: Result<()>) -> Result<()> {
+        *self.last_error.lock().unwrap() =
+            result.as_ref().err().map(|e| e.to_string());
+
+        let status = match &result {
+            Err(e) if is_offline(e) => SyncStatus::Offline,
+            Err(_) => SyncStatus::Error,
+            Ok(()) if !self.conflicts.lock().unwrap().is_empty() => {
+                SyncStatus::Conflict
+            }
+            Ok(()) => match &self.store {
+                Store::Local(_) => SyncStatus::Synced,
+                Store::Cached { cache, .. } => match cache.pending() {
+                    Ok(pending) if pending.is_empty() => SyncStatus::Synced,
+                    _ => SyncStatus::Dirty,
+                },
+            },
+        };
+        self.set_status(status);
+        if let Some(listener) = &*self.listener.lock().unwrap() {
+            listener(&result);
+        }
+        result
+    }
+
+    fn set_status(&self, status: SyncStatus) {
+        *self.status.lock().unwrap() = status;
+    }
+}
+
+/// Whether `error` means the remote could not be reached
+fn is_offline(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<GistError>()
+        .map(|e| e.is_offline())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use anyhow::anyhow;
+    use async_trait::async_trait;
+    use chrono::{Local, TimeZone};
+    use uuid::Uuid;
+
+    fn task(id: &str, content: &str) -> WorkItem {
+        let mut item = WorkItem::new();
+        item.id = Some(id.to_string());
+        item.content = Some(content.to_string());
+        // The cache only keeps whole seconds
+        item.created_time =
+            Local.with_ymd_and_hms(2026, 10, 1, 9, 0, 0).unwrap();
+        item
+    }
+
+    struct TempCache(std::path::PathBuf);
+
+    impl TempCache {
+        fn new() -> Self {
+            TempCache(
+                std::env::temp_dir()
+                    .join(format!("barnacle-{}", Uuid::new_v4())),
+            )
+        }
+
+        fn cache(&self) -> ProjectCache {
+            ProjectCache::new(self.0.clone())
+        }
+    }
+
+    impl Drop for TempCache {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Fails to load `broken`, everything else is kept in memory
+    #[derive(Default)]
+    struct PartlyBroken(MemoryStorage);
+
+    #[async_trait]
+    impl Storage for PartlyBroken {
+        async fn list_projects(&self) -> Result<Vec<String>> {
+            self.0.list_projects().await
+        }
+
+        async fn load_project(&self, project: &str) -> Result<Vec<WorkItem>> {
+            if project == "broken" {
+                return Err(anyhow!("cannot load {}", project));
+            }
+            self.0.load_project(project).await
+        }
+
+        async fn save_project(
+            &self,
+            project: &str,
+            tasks: &[WorkItem],
+        ) -> Result<()> {
+            self.0.save_project(project, tasks).await
+        }
+
+        async fn create_project(&self, project: &str) -> Result<()> {
+            self.0.create_project(project).await
+        }
+
+        async fn delete_project(&self, project: &str) -> Result<()> {
+            self.0.delete_project(project).await
+        }
+    }
+
+    #[tokio::test]
+    async fn edits_without_a_base_do_not_overwrite_the_remote() {
+        let milk = vec![task("1", "buy milk")];
+        let remote = Arc::new(MemoryStorage::default());
+        remote.save_project("inbox", &milk).await.unwrap();
+
+        let dir = TempCache::new();
+        let queue = SyncQueue::new(remote.clone(), dir.cache());
+        let oat_milk = task("1", "buy oat milk");
+        let dog = task("2", "walk dog");
+        queue.save("inbox", &[oat_milk, dog]).unwrap();
+
+        assert!(queue.push().await.is_ok());
+        assert_eq!(queue.status(), SyncStatus::Conflict);
+        assert_eq!(remote.load_project("inbox").await.unwrap(), milk);
+        assert_eq!(queue.conflicted_projects(), vec!["inbox"]);
+
+        let conflict = queue.conflict("inbox").unwrap();
+        assert_eq!(conflict.merged.conflicts.len(), 1);
+        assert_eq!(conflict.merged.conflicts[0].id, "1");
+    }
+
+    #[tokio::test]
+    async fn projects_missing_on_the_remote_are_uploaded() {
+        let remote = Arc::new(MemoryStorage::default());
+        let dir = TempCache::new();
+        let queue = SyncQueue::new(remote.clone(), dir.cache());
+        let tasks = vec![task("1", "buy milk")];
+        queue.save("inbox", &tasks).unwrap();
+
+        assert!(queue.push().await.is_ok());
+        assert_eq!(queue.status(), SyncStatus::Synced);
+        assert_eq!(remote.load_project("inbox").await.unwrap(), tasks);
+    }
+
+    #[tokio::test]
+    async fn remote_tasks_are_kept_when_there_is_no_base() {
+        let milk = vec![task("1", "buy milk")];
+        let remote = Arc::new(MemoryStorage::default());
+        remote.save_project("inbox", &milk).await.unwrap();
+
+        let dir = TempCache::new();
+        let queue = SyncQueue::new(remote.clone(), dir.cache());
+        queue.save("inbox", &[task("2", "walk dog")]).unwrap();
+
+        assert!(queue.push().await.is_ok());
+        assert_eq!(queue.status(), SyncStatus::Synced);
+        let merged = vec![task("2", "walk dog"), task("1", "buy milk")];
+        assert_eq!(remote.load_project("inbox").await.unwrap(), merged);
+        assert_eq!(queue.load("inbox").unwrap(), Some(merged));
+    }
+
+    #[tokio::test]
+    async fn deletes_are_queued_until_pushed() {
+        let remote = Arc::new(MemoryStorage::default());
+        remote
+            .save_project("inbox", &[task("1", "buy milk")])
+            .await
+            .unwrap();
+
+        let dir = TempCache::new();
+        let queue = SyncQueue::new(remote.clone(), dir.cache());
+        assert!(queue.reconcile().await.is_ok());
+        queue.delete("inbox").unwrap();
+
+        assert_eq!(queue.status(), SyncStatus::Dirty);
+        assert!(queue.list_projects().unwrap().is_empty());
+        assert_eq!(remote.list_projects().await.unwrap(), vec!["inbox"]);
+
+        // Pulling before the delete is uploaded must not bring it back
+        assert!(queue.pull().await.is_ok());
+        assert!(queue.list_projects().unwrap().is_empty());
+
+        assert!(queue.push().await.is_ok());
+        assert_eq!(queue.status(), SyncStatus::Synced);
+        assert!(remote.list_projects().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn recreated_projects_start_empty() {
+        let remote = Arc::new(MemoryStorage::default());
+        remote
+            .save_project("inbox", &[task("1", "buy milk")])
+            .await
+            .unwrap();
+
+        let dir = TempCache::new();
+        let queue = SyncQueue::new(remote.clone(), dir.cache());
+        assert!(queue.reconcile().await.is_ok());
+        queue.delete("inbox").unwrap();
+        queue.create("inbox").unwrap();
+
+        assert!(queue.push().await.is_ok());
+        assert_eq!(queue.status(), SyncStatus::Synced);
+        assert!(remote.load_project("inbox").await.unwrap().is_empty());
+        assert_eq!(queue.load("inbox").unwrap(), Some(Vec::new()));
+    }
+
+    #[tokio::test]
+    async fn local_storage_is_written_directly() {
+        let dir = TempCache::new();
+        let queue = SyncQueue::local(LocalStorage::new(dir.0.clone()));
+        let tasks = vec![task("1", "buy milk")];
+        queue.save("inbox", &tasks).unwrap();
+
+        assert_eq!(queue.status(), SyncStatus::Synced);
+        assert_eq!(queue.load("inbox").unwrap(), Some(tasks));
+        let files = std::fs::read_dir(&dir.0).unwrap().count();
+        assert_eq!(files, 1);
+
+        assert!(queue.reconcile().await.is_ok());
+        queue.delete("inbox").unwrap();
+        assert!(queue.list_projects().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn projects_deleted_remotely_are_uploaded_again() {
+        let remote = Arc::new(MemoryStorage::default());
+        let milk = task("1", "buy milk");
+        let report = task("2", "write report");
+        remote.save_project("inbox", &[milk]).await.unwrap();
+        remote.save_project("work", &[report]).await.unwrap();
+
+        let dir = TempCache::new();
+        let queue = SyncQueue::new(remote.clone(), dir.cache());
+        assert!(queue.reconcile().await.is_ok());
+        remote.delete_project("inbox").await.unwrap();
+
+        let inbox = vec![task("1", "buy oat milk")];
+        let work = vec![task("2", "write report"), task("3", "send report")];
+        queue.save("inbox", &inbox).unwrap();
+        queue.save("work", &work).unwrap();
+
+        assert!(queue.push().await.is_ok());
+        assert_eq!(queue.status(), SyncStatus::Synced);
+        assert_eq!(remote.load_project("inbox").await.unwrap(), inbox);
+        assert_eq!(remote.load_project("work").await.unwrap(), work);
+    }
+
+    #[tokio::test]
+    async fn a_failing_project_does_not_hold_back_the_others() {
+        let remote = Arc::new(PartlyBroken::default());
+        remote.save_project("broken", &[]).await.unwrap();
+
+        let dir = TempCache::new();
+        let queue = SyncQueue::new(remote.clone(), dir.cache());
+        let work = vec![task("1", "write report")];
+        queue.save("broken", &[task("2", "walk dog")]).unwrap();
+        queue.save("work", &work).unwrap();
+
+        let error = queue.push().await.unwrap_err();
+        assert_eq!(error.to_string(), "cannot load broken");
+        assert_eq!(queue.status(), SyncStatus::Error);
+        assert_eq!(remote.load_project("work").await.unwrap(), work);
+    }
+}
@@ -1,34 +1,35 @@
-use super::app::{App, AppMode, ItemStatus, WorkItem};
+use super::app::{App, AppMode, ItemStatus, TableLayout, WorkItem};
+use super::command::COMMANDS;
+use super::merge::MergeSide;
+use super::storage::SyncStatus;
 
-
-
-use ratatui::backend::{Backend};
+use ratatui::backend::Backend;
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use ratatui::style::{Color, Modifier, Style};
-use ratatui::text::{Span};
-use ratatui::widgets::{Block, Borders, Paragraph, Row, Table};
+use ratatui::text::{Span, Spans};
+use ratatui::widgets::{
+    Block, Borders, Cell, Clear, Paragraph, Row, Table, Wrap,
+};
 
 use ratatui::Frame;
 
 use chrono::offset::Local;
+use chrono::DateTime;
 use std::fmt::Write;
 
-pub const SMALL_TERMINAL_HEIGHT: u16 = 45;
-
-#[derive(PartialEq)]
+#[derive(PartialEq, Default)]
 pub enum ColumnId {
+    #[default]
     None,
     Id,
+    Status,
     Content,
+    Priority,
+    Tags,
+    Due,
     Days,
 }
 
-impl Default for ColumnId {
-    fn default() -> Self {
-        ColumnId::None
-    }
-}
-
 pub struct TableHeader<'a> {
     items: Vec<TableHeaderItem<'a>>,
 }
@@ -69,40 +70,98 @@ pub fn draw_input_and_help_box<B>(
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints(
-            [Constraint::Percentage(95), Constraint::Percentage(5)].as_ref(),
+            [
+                Constraint::Min(1),
+                Constraint::Length(11),
+                Constraint::Percentage(5),
+            ]
+            .as_ref(),
         )
         .split(layout_chunk);
 
     let mut input_string = String::new();
-    let (_a, b) = match app.mode {
-        AppMode::Global => (write!(input_string, "").unwrap(), 0),
-        AppMode::Command => (
-            write!(input_string, "{}", app.command_bar.buffer()).unwrap(),
-            app.command_bar.input_cursor_position(),
-        ),
-        AppMode::Insert => (
-            write!(input_string, "{}", app.insert_bar.buffer()).unwrap(),
-            app.insert_bar.input_cursor_position(),
-        ),
+    match app.mode {
+        AppMode::Global => match (&app.command_error, &app.pending_delete) {
+            (Some(error), _) => write!(input_string, "{}", error).unwrap(),
+            (None, Some(project)) => {
+                write!(input_string, "Delete project {}? (y/n)", project)
+                    .unwrap()
+            }
+            (None, None) => {
+                write!(input_string, "{}", app.key_sequence.pending()).unwrap()
+            }
+        },
+        AppMode::Resolve => write!(
+            input_string,
+            "l: keep local  r: keep remote  L/R: keep all  Enter: apply"
+        )
+        .unwrap(),
+        AppMode::Command => {
+            write!(input_string, "{}", app.command_bar.buffer()).unwrap()
+        }
+        AppMode::Insert => {
+            write!(input_string, "{}", app.insert_bar.buffer()).unwrap()
+        }
+        AppMode::Search => {
+            write!(input_string, "{}", app.search_bar.buffer()).unwrap()
+        }
+        AppMode::Help => write!(input_string, "j/k: scroll  q: close").unwrap(),
     };
 
     let title = format!("{} Mode:", app.mode);
 
-    
     let p1 = Paragraph::new(Span::raw(input_string))
-        .block(Block::default().borders(Borders::ALL).title(Span::raw(title)))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(Span::raw(title)),
+        )
         .style(match app.mode {
+            AppMode::Global
+                if app.command_error.is_some()
+                    || app.pending_delete.is_some() =>
+            {
+                Style::default().fg(Color::LightRed)
+            }
             AppMode::Global => Style::default(),
             AppMode::Command => Style::default().fg(Color::Yellow),
             AppMode::Insert => Style::default().fg(Color::Green),
+            AppMode::Resolve => Style::default().fg(Color::Red),
+            AppMode::Search => Style::default().fg(Color::Cyan),
+            AppMode::Help => Style::default().fg(Color::LightBlue),
         });
 
     f.render_widget(p1, chunks[0]);
 
-    let p2 = Paragraph::new(Span::raw(b.to_string()))
-        .block(Block::default().borders(Borders::ALL).title("Help:"));
+    let status = app.sync_status();
+    let p2 = Paragraph::new(Span::raw(status.to_string()))
+        .block(Block::default().borders(Borders::ALL).title("Sync:"))
+        .style(match status {
+            SyncStatus::Dirty => Style::default().fg(Color::Yellow),
+            SyncStatus::Syncing => Style::default().fg(Color::LightBlue),
+            SyncStatus::Synced => Style::default().fg(Color::LightGreen),
+            SyncStatus::Offline => Style::default().fg(Color::Red),
+            SyncStatus::Conflict => Style::default().fg(Color::LightRed),
+            SyncStatus::Error => Style::default().fg(Color::LightRed),
+        });
 
     f.render_widget(p2, chunks[1]);
+
+    let p3 = Paragraph::new(Span::raw("?"))
+        .block(Block::default().borders(Borders::ALL).title("Help:"));
+
+    f.render_widget(p3, chunks[2]);
+}
+
+pub fn draw_status_line<B>(f: &mut Frame<B>, app: &App, layout_chunk: Rect)
+where
+    B: Backend,
+{
+    if let Some(message) = app.status_line() {
+        let p = Paragraph::new(Span::raw(message))
+            .style(Style::default().fg(Color::LightRed));
+        f.render_widget(p, layout_chunk);
+    }
 }
 
 pub fn draw_task_list<B>(f: &mut Frame<B>, app: &App, layout_chunk: Rect)
@@ -111,47 +170,92 @@ where
 {
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(100)].as_ref())
+        .constraints(if app.show_details {
+            [Constraint::Percentage(60), Constraint::Percentage(40)].as_ref()
+        } else {
+            [Constraint::Percentage(100)].as_ref()
+        })
         .split(layout_chunk);
 
-    let header = TableHeader {
+    let mut header = TableHeader {
         items: vec![
             TableHeaderItem {
                 id: ColumnId::Id,
                 text: "Id",
-                width: get_percentage_width(layout_chunk.width, 0.2 / 9.0),
+                width: get_percentage_width(chunks[0].width, 0.2 / 9.0),
+            },
+            TableHeaderItem {
+                id: ColumnId::Status,
+                text: "Status",
+                width: get_percentage_width(chunks[0].width, 0.9 / 9.0),
             },
             TableHeaderItem {
                 id: ColumnId::Content,
                 text: "Content",
-                width: get_percentage_width(layout_chunk.width, 8.0 / 9.0),
+                width: get_percentage_width(chunks[0].width, 3.7 / 9.0),
+            },
+            TableHeaderItem {
+                id: ColumnId::Tags,
+                text: "Tags",
+                width: get_percentage_width(chunks[0].width, 1.2 / 9.0),
+            },
+            TableHeaderItem {
+                id: ColumnId::Priority,
+                text: "Priority",
+                width: get_percentage_width(chunks[0].width, 0.6 / 9.0),
+            },
+            TableHeaderItem {
+                id: ColumnId::Due,
+                text: "Due",
+                width: get_percentage_width(chunks[0].width, 0.8 / 9.0),
             },
             TableHeaderItem {
                 text: "Started",
-                width: get_percentage_width(layout_chunk.width, 0.6 / 9.0),
+                width: get_percentage_width(chunks[0].width, 0.6 / 9.0),
                 ..Default::default()
             },
             TableHeaderItem {
                 id: ColumnId::Days,
                 text: "Days",
-                width: get_percentage_width(layout_chunk.width, 1.6 / 6.0),
+                width: 0,
             },
         ],
     };
 
-    let mut current_view = app.get_view();
+    // Days takes what is left, so clicks can be matched to columns
+    let spacing = header.items.len() as u16 - 1;
+    let used = header.items.iter().map(|h| h.width).sum::<u16>() + spacing;
+    if let Some(days) = header.items.last_mut() {
+        days.width = chunks[0].width.saturating_sub(2 + used);
+    }
 
-    current_view.sort_by(|a, b| a.status.partial_cmp(&b.status).unwrap());
+    let current_view = app.get_tree_view();
 
     let messages = current_view
         .iter()
         .enumerate()
-        .map(|(i, m)| TableItem {
+        .map(|(i, (depth, m))| TableItem {
             id: i.to_string(),
             org_item: m,
             format: vec![
                 i.to_string(),
-                m.content.as_ref().unwrap().to_string(),
+                m.status.to_string(),
+                format_tree_content(app, *depth, m),
+                m.tags
+                    .iter()
+                    .map(|t| format!("+{}", t))
+                    .collect::<Vec<String>>()
+                    .join(" "),
+                if m.priority > 0 {
+                    m.priority.to_string()
+                } else {
+                    "-".to_string()
+                },
+                if let Some(due) = m.due {
+                    due.format("%Y-%m-%d").to_string()
+                } else {
+                    "-".to_string()
+                },
                 if let Some(start_time) = m.started_time {
                     start_time.format("%Y-%m-%d").to_string()
                 } else {
@@ -174,9 +278,73 @@ where
         chunks[0],
         &header,
         &messages,
-        app.selected_index,
+        app.selected_index(),
         (false, false),
     );
+
+    if app.show_details {
+        draw_task_details(f, app, chunks[1]);
+    }
+}
+
+/// Everything about the selected task, including its notes
+pub fn draw_task_details<B>(f: &mut Frame<B>, app: &App, layout_chunk: Rect)
+where
+    B: Backend,
+{
+    let mut lines = Vec::new();
+
+    if let Some(task) = app.selected_task() {
+        let format_time = |time: Option<DateTime<Local>>| {
+            time.map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| "-".to_string())
+        };
+        let label = Style::default().add_modifier(Modifier::BOLD);
+        let field = |name: &'static str, value: String| {
+            Spans::from(vec![
+                Span::styled(format!("{:<10}", name), label),
+                Span::raw(value),
+            ])
+        };
+
+        lines.push(Spans::from(Span::styled(
+            task.content.clone().unwrap_or_default(),
+            label,
+        )));
+        lines.push(Spans::default());
+        lines.push(field("Id", task.id.clone().unwrap_or_default()));
+        lines.push(field("Status", task.status.to_string()));
+        lines.push(field("Priority", task.priority.to_string()));
+        lines.push(field("Tags", task.tags.join(" ")));
+        lines.push(field(
+            "Due",
+            task.due
+                .map(|d| d.format("%Y-%m-%d").to_string())
+                .unwrap_or_else(|| "-".to_string()),
+        ));
+        lines.push(field(
+            "Parent",
+            task.parent_id.clone().unwrap_or_else(|| "-".to_string()),
+        ));
+        lines.push(field("Created", format_time(Some(task.created_time))));
+        lines.push(field("Started", format_time(task.started_time)));
+        lines.push(field("Finished", format_time(task.finished_time)));
+        lines.push(Spans::default());
+        lines.push(Spans::from(Span::styled("Notes", label)));
+        match &task.notes {
+            Some(notes) => lines.extend(notes.lines().map(Spans::from)),
+            None => lines.push(Spans::from("Press e to add notes")),
+        }
+    }
+
+    let p = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(Span::raw("Details:")),
+        )
+        .wrap(Wrap { trim: false });
+    f.render_widget(p, layout_chunk);
 }
 
 pub fn draw_core_layout<B>(f: &mut Frame<B>, app: &App, layout: &Layout)
@@ -185,22 +353,236 @@ where
 {
     let parent_layout: std::rc::Rc<[Rect]> = layout.split(f.size());
 
-    draw_task_list(f, app, parent_layout[0]);
+    match app.mode {
+        AppMode::Resolve => draw_resolution(f, app, parent_layout[0]),
+        AppMode::Help => {
+            draw_task_list(f, app, parent_layout[0]);
+            draw_help(f, app, parent_layout[0]);
+        }
+        _ => draw_task_list(f, app, parent_layout[0]),
+    }
     draw_input_and_help_box(f, app, parent_layout[1]);
+    draw_status_line(f, app, parent_layout[2]);
+    if let AppMode::Command = app.mode {
+        draw_completion(f, app, parent_layout[1]);
+    }
 
     match app.mode {
-        AppMode::Global => {
-            {}
-        },
+        AppMode::Global | AppMode::Resolve | AppMode::Help => {}
         _ => {
             // Put the cursor back inside the input box
-            f.set_cursor(
-                1 + app.get_cursor_position(),
-                parent_layout[1].y + 1,
-            );
-        },
+            f.set_cursor(1 + app.get_cursor_position(), parent_layout[1].y + 1);
+        }
     };
+}
 
+/// Keys and commands in a popup over the task table
+pub fn draw_help<B>(f: &mut Frame<B>, app: &App, layout_chunk: Rect)
+where
+    B: Backend,
+{
+    let popup = Rect {
+        x: layout_chunk.x + layout_chunk.width / 10,
+        y: layout_chunk.y + layout_chunk.height / 10,
+        width: layout_chunk.width - layout_chunk.width / 5,
+        height: layout_chunk.height - layout_chunk.height / 5,
+    };
+    let heading = Style::default().add_modifier(Modifier::BOLD);
+    let key = Style::default().fg(Color::Yellow);
+
+    let mut lines = vec![Spans::from(Span::styled("Keys", heading))];
+    for (keys, help) in app.keymap.help() {
+        lines.push(Spans::from(vec![
+            Span::styled(format!("{:<22}", keys), key),
+            Span::raw(help),
+        ]));
+    }
+
+    lines.push(Spans::default());
+    lines.push(Spans::from(Span::styled("Commands", heading)));
+    for command in COMMANDS.iter() {
+        let names = std::iter::once(&command.name)
+            .chain(command.aliases.iter())
+            .map(|name| format!(":{}", name))
+            .collect::<Vec<String>>();
+        let usage = format!("{} {}", names.join(", "), command.args);
+        lines.push(Spans::from(vec![
+            Span::styled(format!("{:<22}", usage), key),
+            Span::raw(command.help),
+        ]));
+    }
+
+    // Stop once the last line is at the bottom of the popup
+    let max_scroll = lines
+        .len()
+        .saturating_sub(usize::from(popup.height.saturating_sub(2)));
+    app.help_max_scroll.set(max_scroll);
+    let scroll = app.help_scroll.min(max_scroll);
+    let p = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(Span::raw("Help:")),
+        )
+        .scroll((scroll as u16, 0));
+
+    f.render_widget(Clear, popup);
+    f.render_widget(p, popup);
+}
+
+/// How many completion candidates are shown at once
+const COMPLETION_HEIGHT: usize = 8;
+
+/// Candidates of the command bar in a popup just above the input box
+pub fn draw_completion<B>(f: &mut Frame<B>, app: &App, input_chunk: Rect)
+where
+    B: Backend,
+{
+    let completion = match &app.completion {
+        Some(completion) => completion,
+        None => return,
+    };
+
+    let selected = completion.selected.unwrap_or(0);
+    let first = selected.saturating_sub(COMPLETION_HEIGHT - 1);
+    let lines = completion
+        .candidates
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(COMPLETION_HEIGHT)
+        .map(|(i, candidate)| {
+            let style = if completion.selected == Some(i) {
+                Style::default().fg(Color::Black).bg(Color::Yellow)
+            } else {
+                Style::default()
+            };
+            Spans::from(Span::styled(candidate.clone(), style))
+        })
+        .collect::<Vec<Spans>>();
+
+    let width = completion
+        .candidates
+        .iter()
+        .map(|c| c.chars().count())
+        .max()
+        .unwrap_or(0) as u16
+        + 2;
+    let height = (lines.len() as u16 + 2).min(input_chunk.y);
+    let popup = Rect {
+        x: input_chunk.x,
+        y: input_chunk.y - height,
+        width: width.min(input_chunk.width),
+        height,
+    };
+
+    let p = Paragraph::new(lines).block(Block::default().borders(Borders::ALL));
+    f.render_widget(Clear, popup);
+    f.render_widget(p, popup);
+}
+
+pub fn draw_resolution<B>(f: &mut Frame<B>, app: &App, layout_chunk: Rect)
+where
+    B: Backend,
+{
+    let resolution = match &app.resolution {
+        Some(resolution) => resolution,
+        None => return,
+    };
+
+    let selected_style = Style::default()
+        .fg(Color::LightBlue)
+        .add_modifier(Modifier::BOLD);
+
+    let rows = resolution
+        .conflict
+        .merged
+        .conflicts
+        .iter()
+        .zip(resolution.choices.iter())
+        .enumerate()
+        .map(|(i, (conflict, choice))| {
+            let task = conflict
+                .local
+                .as_ref()
+                .or(conflict.remote.as_ref())
+                .and_then(|t| t.content.clone())
+                .unwrap_or_default();
+            let keep = match choice {
+                Some(MergeSide::Local) => "local",
+                Some(MergeSide::Remote) => "remote",
+                None => "-",
+            };
+
+            let style = if i == resolution.selected_index {
+                selected_style
+            } else if choice.is_none() {
+                Style::default().fg(Color::Red)
+            } else {
+                Style::default()
+            };
+
+            Row::new(vec![
+                task,
+                conflict.field.to_string(),
+                conflict.field.describe(conflict.local.as_ref()),
+                conflict.field.describe(conflict.remote.as_ref()),
+                keep.to_string(),
+            ])
+            .style(style)
+        });
+
+    let widths = [
+        Constraint::Percentage(30),
+        Constraint::Length(8),
+        Constraint::Percentage(25),
+        Constraint::Percentage(25),
+        Constraint::Length(7),
+    ];
+
+    let title = format!("{}(Conflicts):", resolution.project);
+
+    let t1 = Table::new(rows)
+        .header(Row::new(vec!["Task", "Field", "Local", "Remote", "Keep"]))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default())
+                .title(Span::raw(title)),
+        )
+        .style(Style::default())
+        .widths(&widths);
+
+    f.render_widget(t1, layout_chunk);
+}
+
+/// Content indented by `depth` with a fold marker and progress for parents
+fn format_tree_content(app: &App, depth: usize, item: &WorkItem) -> String {
+    let content = item.content.as_deref().unwrap_or("");
+    let indent = "  ".repeat(depth);
+    let id = item.id.as_deref().unwrap_or("");
+
+    match app.child_progress(id) {
+        Some((closed, total)) => {
+            let marker = if app.collapsed.contains(id) {
+                '▸'
+            } else {
+                '▾'
+            };
+            format!("{}{} {} ({}/{})", indent, marker, content, closed, total)
+        }
+        None if depth > 0 => format!("{}  {}", indent, content),
+        None => content.to_string(),
+    }
+}
+
+fn priority_color(priority: usize) -> Option<Color> {
+    match priority {
+        0 => None,
+        1 => Some(Color::LightBlue),
+        2 => Some(Color::Yellow),
+        _ => Some(Color::LightRed),
+    }
 }
 
 fn draw_table<B>(
@@ -227,15 +609,22 @@ fn draw_table<B>(
         .and_then(|height| selected_index.checked_sub(height as usize))
         .unwrap_or(0);
 
+    let today = Local::now().date_naive();
+
     let rows = items.iter().skip(offset).enumerate().map(|(i, item)| {
-        let formatted_row = item.format.clone();
+        let mut cells = item
+            .format
+            .iter()
+            .map(|text| Cell::from(text.clone()))
+            .collect::<Vec<Cell>>();
         let mut style = Style::default(); // default styling
 
         // TODO: May want to change the style if its been sitting to many days
         if let Some(_title_idx) = header.get_index(ColumnId::Content) {
             match item.org_item.status {
                 ItemStatus::WontFix => {
-                    style = style.fg(Color::Red).add_modifier(Modifier::CROSSED_OUT)
+                    style =
+                        style.fg(Color::Red).add_modifier(Modifier::CROSSED_OUT)
                 }
                 ItemStatus::Started => style = style.fg(Color::LightGreen),
                 ItemStatus::Finished => {
@@ -243,17 +632,30 @@ fn draw_table<B>(
                 }
                 _ => {}
             }
+
+            if item.org_item.is_overdue(today) {
+                style = style.fg(Color::Red).add_modifier(Modifier::BOLD);
+            }
         }
 
-        // TODO: May want to change the style if its been sitting to many days
-        if header.get_index(ColumnId::Days).is_some() {}
+        if let Some(priority_idx) = header.get_index(ColumnId::Priority) {
+            if let Some(color) = priority_color(item.org_item.priority) {
+                cells[priority_idx] =
+                    Cell::from(item.format[priority_idx].clone())
+                        .style(Style::default().fg(color));
+            }
+        }
+
+        if app.is_search_match(item.org_item) {
+            style = style.fg(Color::Black).bg(Color::Yellow);
+        }
 
         // Next check if the item is under selection.
         if Some(i) == selected_index.checked_sub(offset) {
             style = selected_style;
         }
 
-        Row::new(formatted_row.into_iter()).style(style)
+        Row::new(cells).style(style)
     });
 
     let widths = header
@@ -262,13 +664,19 @@ fn draw_table<B>(
         .map(|h| Constraint::Length(h.width))
         .collect::<Vec<ratatui::layout::Constraint>>();
 
+    let filter = if app.tag_filter.is_empty() {
+        app.filter.to_string()
+    } else {
+        format!("{} {}", app.filter, app.tag_filter)
+    };
+
     let title = format!(
         "{}({}):",
         app.current_project.as_ref().unwrap_or(&"Tasks".to_string()),
-        app.filter,
+        filter,
     );
 
-    let h = Row::new(header.items.iter().map(|h| h.text.clone()));
+    let h = Row::new(header.items.iter().map(|h| h.text));
 
     let t1 = Table::new(rows)
         .header(h)
@@ -282,4 +690,27 @@ fn draw_table<B>(
         .widths(&widths);
 
     f.render_widget(t1, layout_chunk);
+
+    // Columns are one space apart, starting inside the border
+    let table_area = Block::default().borders(Borders::ALL).inner(layout_chunk);
+    let status_column = header
+        .get_index(ColumnId::Status)
+        .map(|index| {
+            let x = header.items[..index]
+                .iter()
+                .map(|h| h.width + 1)
+                .sum::<u16>();
+            (table_area.x + x, header.items[index].width)
+        })
+        .unwrap_or_default();
+
+    app.table_layout.set(TableLayout {
+        rows: Rect {
+            y: table_area.y + 1,
+            height: table_area.height.saturating_sub(1),
+            ..table_area
+        },
+        offset,
+        status_column,
+    });
 }